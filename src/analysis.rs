@@ -0,0 +1,595 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::declaration::{Declaration, FnArg};
+use crate::ast::expression::Expr;
+use crate::ast::statement::Statement;
+use crate::ast::visitor::{walk_expr, walk_statement, Visitor};
+use crate::std::Std;
+
+/// A non-fatal diagnostic produced by a static analysis pass
+#[derive(Debug, PartialEq, Eq)]
+pub enum AnalysisWarning {
+    /// A variable is assigned in a function but its value is never read
+    UnusedVariable { function: String, variable: String },
+    /// A function can't be reached from `main`
+    UnusedFunction { function: String },
+    /// A comparison is evaluated as a statement, so its result is discarded.
+    /// It may be an assignment written with `==` instead of `=`.
+    DiscardedComparison { function: String, comparison: String },
+    /// A variable is read in a function but it is not defined at this point
+    UndefinedVariable { function: String, variable: String },
+    /// A statement follows a `return` or a `break` in its block, so it is never evaluated
+    Unreachable { function: String, statement: String },
+}
+
+/// Reports the variables of a function that are assigned but never read.
+pub fn unused_variables(declaration: &Declaration) -> Vec<AnalysisWarning> {
+    let mut pass = UnusedVariables { scopes: vec![], unused: vec![] };
+    pass.visit_declaration(declaration);
+    let function = declaration.name();
+    pass.unused.into_iter()
+        .map(|variable| AnalysisWarning::UnusedVariable { function: function.to_string(), variable })
+        .collect()
+}
+
+/// Reports the statements of a function that only compare values, such as `x == 1;`
+pub fn discarded_comparisons(declaration: &Declaration) -> Vec<AnalysisWarning> {
+    let mut pass = DiscardedComparisons { comparisons: vec![] };
+    pass.visit_declaration(declaration);
+    let function = declaration.name();
+    pass.comparisons.into_iter()
+        .map(|comparison| AnalysisWarning::DiscardedComparison { function: function.to_string(), comparison })
+        .collect()
+}
+
+/// Reports the variables of a function that are read before being assigned, or outside
+/// of the block in which they are assigned. The global names are the names that are always
+/// defined: the constants and the functions of the module.
+pub fn undefined_variables(declaration: &Declaration, globals: &BTreeSet<&str>) -> Vec<AnalysisWarning> {
+    let mut pass = UndefinedVariables { globals, scopes: vec![], undefined: vec![] };
+    pass.visit_declaration(declaration);
+    let function = declaration.name();
+    pass.undefined.into_iter()
+        .map(|variable| AnalysisWarning::UndefinedVariable { function: function.to_string(), variable })
+        .collect()
+}
+
+/// Reports the statements of a function that follow a statement that always exits its block
+pub fn unreachable_statements(declaration: &Declaration) -> Vec<AnalysisWarning> {
+    let mut pass = UnreachableStatements { statements: vec![] };
+    pass.visit_declaration(declaration);
+    let function = declaration.name();
+    pass.statements.into_iter()
+        .map(|statement| AnalysisWarning::Unreachable { function: function.to_string(), statement })
+        .collect()
+}
+
+struct UnreachableStatements {
+    statements: Vec<String>,
+}
+
+impl Visitor for UnreachableStatements {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::CompoundStatement(statements) => {
+                let reachable = statements.iter().position(Statement::always_exits).map_or(statements.len(), |exit| exit + 1);
+                for statement in &statements[..reachable] {
+                    self.visit_statement(statement);
+                }
+                self.statements.extend(statements[reachable..].iter().map(|statement| statement.to_string()));
+            }
+            _ => walk_statement(self, statement)
+        }
+    }
+}
+
+struct DiscardedComparisons {
+    comparisons: Vec<String>,
+}
+
+impl Visitor for DiscardedComparisons {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if let Statement::SimpleStatement(expr @ (Expr::CompareExpr(..) | Expr::ChainedCompare(..))) = statement {
+            self.comparisons.push(expr.to_string());
+        }
+        walk_statement(self, statement)
+    }
+}
+
+/// Returns the names of the functions called by a function, or referred to as values.
+/// The names are sorted, and the names of the standard library are ignored.
+pub fn called_functions(declaration: &Declaration) -> Vec<String> {
+    let mut pass = CalledFunctions { names: BTreeSet::new() };
+    pass.visit_declaration(declaration);
+    pass.names.into_iter().collect()
+}
+
+struct CalledFunctions {
+    names: BTreeSet<String>,
+}
+
+impl Visitor for CalledFunctions {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::FunctionCall(name, _, _) | Expr::IdentExpr(name) if !Std::is_in_standard_lib(name) => {
+                self.names.insert(name.to_string());
+            }
+            _ => {}
+        }
+        walk_expr(self, expr)
+    }
+}
+
+/// Returns whether a function body never calls, nor refers to, an impure function of the
+/// standard library. Since functions can't modify the variables of their caller, such a
+/// function always returns the same value for the same arguments, unless it calls an impure
+/// function of the module.
+pub fn is_pure(body: &Statement) -> bool {
+    let mut pass = ImpureFunctions { found: false };
+    pass.visit_statement(body);
+    !pass.found
+}
+
+struct ImpureFunctions {
+    found: bool,
+}
+
+impl Visitor for ImpureFunctions {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::FunctionCall(name, _, _) | Expr::IdentExpr(name) if Std::is_impure(name) => self.found = true,
+            _ => {}
+        }
+        walk_expr(self, expr)
+    }
+}
+
+/// Returns whether a `break value;` gives its value to a `while`, `repeat` or `do` loop,
+/// where the value would be dropped. The `break` of a `loop` can give it a value.
+pub fn breaks_loop_statement_with_value(body: &Statement) -> bool {
+    let mut pass = LoopStatementBreaks { in_loop_statement: false, found: false };
+    pass.visit_statement(body);
+    pass.found
+}
+
+struct LoopStatementBreaks {
+    /// Whether the innermost loop is a `while`, `repeat` or `do` loop
+    in_loop_statement: bool,
+    found: bool,
+}
+
+impl LoopStatementBreaks {
+    /// Visits a statement in which `break` targets a different loop, or none
+    fn visit_inner(&mut self, in_loop_statement: bool, visit: impl FnOnce(&mut Self)) {
+        let outer = std::mem::replace(&mut self.in_loop_statement, in_loop_statement);
+        visit(self);
+        self.in_loop_statement = outer;
+    }
+}
+
+impl Visitor for LoopStatementBreaks {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Break(Some(_)) if self.in_loop_statement => self.found = true,
+            Statement::Repeat(..) | Statement::While(..) | Statement::DoWhile(..) => {
+                return self.visit_inner(true, |pass| walk_statement(pass, statement))
+            }
+            // The `break` of a `loop`, or of a local function, does not leave the enclosing loop
+            Statement::Loop(_) | Statement::LocalFn(..) => {
+                return self.visit_inner(false, |pass| walk_statement(pass, statement))
+            }
+            _ => {}
+        }
+        walk_statement(self, statement)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Loop(_) | Expr::Closure(..) => self.visit_inner(false, |pass| walk_expr(pass, expr)),
+            expr => walk_expr(self, expr),
+        }
+    }
+}
+
+/// Returns whether an expression evaluated outside of a function, such as the value of a global
+/// constant, contains a `return`. It can only be in the body of a loop expression, since the
+/// closures return from themselves.
+pub fn returns_outside_function(expr: &Expr) -> bool {
+    let mut pass = OuterReturns { found: false };
+    pass.visit_expr(expr);
+    pass.found
+}
+
+struct OuterReturns {
+    found: bool,
+}
+
+impl Visitor for OuterReturns {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Return(_) => self.found = true,
+            Statement::LocalFn(..) => {}
+            statement => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Closure(..) => {}
+            expr => walk_expr(self, expr),
+        }
+    }
+}
+
+/// Reports the functions of a call graph that can't be reached from the entry function.
+/// Since functions passed as values are part of the call graph, a function that is only
+/// called through a variable is still reachable.
+pub fn unreachable_functions(call_graph: &HashMap<String, Vec<String>>, entry: &str) -> Vec<AnalysisWarning> {
+    let mut reachable = BTreeSet::new();
+    let mut to_visit = vec![entry];
+    while let Some(function) = to_visit.pop() {
+        if reachable.insert(function) {
+            if let Some(called) = call_graph.get(function) {
+                to_visit.extend(called.iter().map(String::as_str));
+            }
+        }
+    }
+    let mut unreachable: Vec<&String> = call_graph.keys().filter(|function| !reachable.contains(function.as_str())).collect();
+    unreachable.sort();
+    unreachable.into_iter()
+        .map(|function| AnalysisWarning::UnusedFunction { function: function.clone() })
+        .collect()
+}
+
+/// Follows the scoping rules of the evaluation: a block `{}` opens a new scope, in which the
+/// assignments create new variables unless they are already defined in an enclosing scope.
+struct UnusedVariables {
+    /// Variables of each scope, with whether they have been read
+    scopes: Vec<Vec<(String, bool)>>,
+    unused: Vec<String>,
+}
+
+impl UnusedVariables {
+    fn push_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.unused.extend(scope.into_iter().filter(|(_, read)| !read).map(|(name, _)| name));
+        }
+    }
+
+    fn assign(&mut self, name: &str) {
+        if self.scopes.iter().any(|scope| scope.iter().any(|(variable, _)| variable == name)) {
+            return;
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.to_string(), false));
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some((_, read)) = scope.iter_mut().find(|(variable, _)| variable == name) {
+                *read = true;
+                return;
+            }
+        }
+    }
+}
+
+impl Visitor for UnusedVariables {
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Function(_, args, body) | Declaration::ConstFunction(_, args, body) => {
+                // Arguments are not assigned in the body, so they are never reported
+                self.scopes.push(args.iter().map(|arg| (arg.0.to_string(), true)).collect());
+                self.visit_statement(body);
+                self.scopes.pop();
+            }
+            // Constants and structs have no variables
+            Declaration::Const(..) | Declaration::Struct(..) => {}
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::CompoundStatement(statements) => {
+                self.push_scope();
+                for statement in statements {
+                    self.visit_statement(statement);
+                }
+                self.pop_scope();
+            }
+            // The body of a local function has its own variables
+            Statement::LocalFn(_, args, body) => {
+                self.scopes.push(args.iter().map(|arg| (arg.0.to_string(), true)).collect());
+                self.visit_statement(body);
+                self.scopes.pop();
+            }
+            Statement::Repeat(count, body) => {
+                self.visit_expr(count);
+                self.visit_statement(body);
+            }
+            // The condition is read again after each iteration
+            Statement::While(condition, body) => {
+                self.visit_expr(condition);
+                self.visit_statement(body);
+                self.visit_expr(condition);
+            }
+            _ => walk_statement(self, statement)
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::AssignmentExpr(name, value) => {
+                self.visit_expr(value);
+                self.assign(name);
+            }
+            Expr::IdentExpr(name) => self.read(name),
+            // The called function may be held by a variable
+            Expr::FunctionCall(name, _, _) => {
+                self.read(name);
+                walk_expr(self, expr);
+            }
+            Expr::ListAccess(name, index) => {
+                self.read(name);
+                self.visit_expr(index);
+            }
+            // The body of a closure reads the variables it captures
+            Expr::Closure(args, body) => {
+                self.scopes.push(args.iter().map(|arg| (arg.0.to_string(), true)).collect());
+                self.visit_statement(body);
+                self.scopes.pop();
+            }
+            _ => walk_expr(self, expr)
+        }
+    }
+}
+
+/// Follows the same scoping rules as [`UnusedVariables`]. The body of a closure or of a local
+/// function sees the variables defined where it is defined, since it captures them.
+struct UndefinedVariables<'a> {
+    globals: &'a BTreeSet<&'a str>,
+    /// Variables defined in each scope
+    scopes: Vec<Vec<String>>,
+    /// Undefined variables, in the order of their first read
+    undefined: Vec<String>,
+}
+
+impl UndefinedVariables<'_> {
+    fn is_defined(&self, name: &str) -> bool {
+        self.globals.contains(name)
+            || Std::is_in_standard_lib(name)
+            || self.scopes.iter().any(|scope| scope.iter().any(|variable| variable == name))
+    }
+
+    fn define(&mut self, name: &str) {
+        if !self.is_defined(name) {
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.push(name.to_string());
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        if !self.is_defined(name) && !self.undefined.iter().any(|variable| variable == name) {
+            self.undefined.push(name.to_string());
+        }
+    }
+
+    /// Visits a function body, in which the arguments are defined
+    fn visit_function(&mut self, args: &[FnArg], body: &Statement) {
+        self.scopes.push(args.iter().map(|arg| arg.0.to_string()).collect());
+        self.visit_statement(body);
+        self.scopes.pop();
+    }
+}
+
+impl Visitor for UndefinedVariables<'_> {
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Function(_, args, body) | Declaration::ConstFunction(_, args, body) => self.visit_function(args, body),
+            // Constants are evaluated before the functions exist, they are checked when evaluating the module
+            Declaration::Const(..) | Declaration::Struct(..) => {}
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::CompoundStatement(statements) => {
+                self.scopes.push(vec![]);
+                for statement in statements {
+                    self.visit_statement(statement);
+                }
+                self.scopes.pop();
+            }
+            // The function can call itself
+            Statement::LocalFn(name, args, body) => {
+                self.define(name);
+                self.visit_function(args, body);
+            }
+            _ => walk_statement(self, statement)
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::AssignmentExpr(name, value) => {
+                self.visit_expr(value);
+                self.define(name);
+            }
+            Expr::IdentExpr(name) => self.read(name),
+            Expr::ListAccess(name, index) => {
+                self.read(name);
+                self.visit_expr(index);
+            }
+            Expr::Closure(args, body) => self.visit_function(args, body),
+            _ => walk_expr(self, expr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::AnalysisWarning;
+    use crate::parser::Parser;
+    use crate::token::tokenize;
+
+    fn get_unused_variables(text: &str) -> Vec<String> {
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        module.unused_variables().into_iter()
+            .filter_map(|warning| match warning {
+                AnalysisWarning::UnusedVariable { variable, .. } => Some(variable),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        let unused = get_unused_variables("fn main() { a = 1; b = 2; return a; }");
+        assert_eq!(unused, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_unused_variable_function_name() {
+        let tokens = tokenize("fn foo(n) { a = n; }").unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(
+            module.unused_variables(),
+            vec![AnalysisWarning::UnusedVariable { function: "foo".to_string(), variable: "a".to_string() }]
+        );
+    }
+
+    #[test]
+    fn test_unused_variable_in_nested_scopes() {
+        // Inside a block, the assignment updates the outer variable
+        let unused = get_unused_variables("fn main() { a = 1; { a = 2; } return a; }");
+        assert!(unused.is_empty());
+        let unused = get_unused_variables("fn main() { { a = 1; { a = 2; } } }");
+        assert_eq!(unused, vec!["a".to_string()]);
+
+        // Variables of the outer scope can be read from an inner block
+        let unused = get_unused_variables("fn main() { a = 1; { b = a; return b; } }");
+        assert!(unused.is_empty());
+
+        // A variable is only visible in its own block
+        let unused = get_unused_variables("fn main() { { b = 1; } { b = 2; return b; } }");
+        assert_eq!(unused, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_unused_variable_in_loops() {
+        // The body of a loop runs in the enclosing scope
+        let text = std::fs::read_to_string("TestData/fibonacci.txt").unwrap();
+        assert!(get_unused_variables(&text).is_empty());
+
+        let unused = get_unused_variables("fn main(n) { i = 0; loop { i = i + 1; j = i; if (i == n) { break; } } }");
+        assert_eq!(unused, vec!["j".to_string()]);
+    }
+
+    fn get_undefined_variables(text: &str) -> Vec<String> {
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        module.undefined_variables().into_iter()
+            .filter_map(|warning| match warning {
+                AnalysisWarning::UndefinedVariable { variable, .. } => Some(variable),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let tokens = tokenize("fn main() { count = 1; return cuont + 1; }").unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(
+            module.undefined_variables(),
+            vec![AnalysisWarning::UndefinedVariable { function: "main".to_string(), variable: "cuont".to_string() }]
+        );
+
+        // Arguments, functions, global constants and the standard library are defined
+        let text = "const N = 3; fn foo(x) { return x; } fn main(a) { f = foo; return [a, N, f, foo, len, f(1)]; }";
+        assert!(get_undefined_variables(text).is_empty());
+
+        // Variables must be assigned before being read, and are reported once per function
+        assert_eq!(get_undefined_variables("fn main() { b = a; a = 1; return a + b + c + c[0]; }"), vec!["a", "c"]);
+        assert_eq!(get_undefined_variables("fn foo() { return a; } fn main() { a = 1; return foo(); }"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_undefined_variable_in_nested_scopes() {
+        // A variable is only visible in its own block
+        assert_eq!(get_undefined_variables("fn main(c) { if (c) { a = 1; } return a; }"), vec!["a"]);
+        assert!(get_undefined_variables("fn main(c) { a = 0; if (c) { a = 1; } return a; }").is_empty());
+        // Each iteration of a loop has its own scope
+        assert_eq!(get_undefined_variables("fn main() { repeat 2 { print(a); a = 1; } }"), vec!["a"]);
+
+        // Closures and local functions see their arguments and the variables they capture
+        assert!(get_undefined_variables("fn main() { n = 1; f = fn(x) { return x + n; }; return f(1); }").is_empty());
+        assert!(get_undefined_variables("fn main() { fn fact(n) { if (n == 0) { return 1; } return n * fact(n - 1); } return fact(3); }").is_empty());
+        assert_eq!(get_undefined_variables("fn main() { f = fn(x) { y = x; return y; }; return y; }"), vec!["y"]);
+    }
+
+    #[test]
+    fn test_unreachable_statements() {
+        let tokens = tokenize("fn main() { x = 1; return x; x = 2; print(x); }").unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let warning = |statement: &str| AnalysisWarning::Unreachable { function: "main".to_string(), statement: statement.to_string() };
+        assert_eq!(module.unreachable_statements(), vec![warning("x = 2;"), warning("print(x);")]);
+
+        let unreachable = |text: &str| {
+            let tokens = tokenize(text).unwrap();
+            Parser::new(&tokens).parse_module().unwrap().unreachable_statements().len()
+        };
+        // Both branches exit
+        assert_eq!(unreachable("fn main(c) { if (c) { return 1; } else { return 2; } print(c); }"), 1);
+        assert_eq!(unreachable("fn main(c) { if (c) { return 1; } print(c); }"), 0);
+        assert_eq!(unreachable("fn main(c) { match c { 1 => { return 1; } _ => { return 2; } } print(c); }"), 1);
+        assert_eq!(unreachable("fn main(c) { match c { 1 => { return 1; } } print(c); }"), 0);
+        // A `break` only leaves its loop
+        assert_eq!(unreachable("fn main() { loop { break; print(1); } return 1; }"), 1);
+        assert_eq!(unreachable("fn main() { loop { { break; } print(1); } return 1; }"), 1);
+        assert_eq!(unreachable("fn main() { f = fn() { return 1; return 2; }; return f(); }"), 1);
+    }
+
+    #[test]
+    fn test_discarded_comparisons() {
+        let tokens = tokenize("fn main() { x = 1; x == 1; if (x == 1) { y = x < 2; loop { x >= 2; break; } } return x == 1; }").unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let warning = |comparison: &str| AnalysisWarning::DiscardedComparison { function: "main".to_string(), comparison: comparison.to_string() };
+        assert_eq!(module.discarded_comparisons(), vec![warning("x == 1"), warning("x >= 2")]);
+    }
+
+    #[test]
+    fn test_unused_functions() {
+        let text = "\
+fn orphan() { return helper(); }
+fn helper() { return 1; }
+fn double(x) { return 2 * x; }
+fn foo() { return map(double, [1]); }
+fn main() { return foo(); }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        // `helper` is only called by an unreachable function, `double` is reachable as a value
+        assert_eq!(
+            module.unused_functions(),
+            vec![
+                AnalysisWarning::UnusedFunction { function: "helper".to_string() },
+                AnalysisWarning::UnusedFunction { function: "orphan".to_string() },
+            ]
+        );
+
+        // Without `main`, every function may be called
+        let tokens = tokenize("fn orphan() { return 1; }").unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert!(module.unused_functions().is_empty());
+    }
+}
@@ -1,24 +1,26 @@
 pub mod expression;
 pub mod statement;
 pub mod declaration;
+pub mod visitor;
+pub mod printer;
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
     use crate::ast::expression::Value::IntValue;
 
+    use crate::environment::Environment;
     use crate::parser::parse_expression;
     use crate::token::*;
 
     fn assert_ast_eval(text: &str, expected: i64) {
-        let tokens = tokenize(&text.to_string());
+        let tokens = tokenize(text);
         if let Ok(ast) = parse_expression(&tokens.unwrap()) {
-            match ast.eval(&mut HashMap::new(), None) {
+            match ast.eval(&mut Environment::new(), None) {
                 Ok(IntValue(value)) => assert_eq!(value, expected),
-                _ => assert!(false),
+                result => panic!("Expected an integer, got {result:?}"),
             }
         } else {
-            assert!(false);
+            panic!("The expression could not be parsed");
         }
     }
 
@@ -1,43 +1,73 @@
-use std::collections::HashMap;
-use crate::ast::expression::Value;
 
-use crate::ast::statement::{Statement, StatementEval};
+use crate::ast::expression::Expr;
+use crate::ast::statement::{Statement, StatementEval, StatementHook};
+use crate::environment::Environment;
 use crate::error::EvalError;
 use crate::module::Module;
+use crate::symbol::Symbol;
 
 /// A function argument currently only contains a string
-#[derive(Debug)]
-pub struct FnArg(pub String);
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct FnArg(pub Symbol);
 
 /// A declaration is the top-level element of a file.
 /// A file is list of declaration
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Declaration {
     /// A function = name + list of expression (arguments) + list of statement
-    Function(String, Vec<FnArg>, Statement)
+    Function(Symbol, Vec<FnArg>, Statement),
+    /// A pure function, marked with `const fn`, whose calls with constant arguments are
+    /// evaluated by the optimizer
+    ConstFunction(Symbol, Vec<FnArg>, Statement),
+    /// A global constant = name + value, visible in all the functions of the module
+    Const(Symbol, Expr),
+    /// A struct = name + names of its fields
+    Struct(Symbol, Vec<Symbol>),
 }
 
 impl Declaration {
+    /// Name of the function, of the constant or of the struct
+    pub fn name(&self) -> &str {
+        match self {
+            Declaration::Function(name, _, _) | Declaration::ConstFunction(name, _, _)
+            | Declaration::Const(name, _) | Declaration::Struct(name, _) => name.as_str(),
+        }
+    }
+
     /// Evaluate the output of the function based on the provided arguments
     /// Inputs are the inputs of the function
-    pub fn eval(&self, inputs: &mut HashMap<String, Value>, module: Option<&Module>) -> Result<StatementEval, EvalError> {
-        return match self {
-            Declaration::Function(_name, _args, body) => {
+    pub fn eval(&self, inputs: &mut Environment, module: Option<&Module>) -> Result<StatementEval, EvalError> {
+        self.eval_with_hook(inputs, module, None)
+    }
+
+    /// Evaluates the declaration like `eval`, calling the hook with the value of each expression
+    /// statement of the body of the function, see `Statement::eval_with_hook`
+    pub fn eval_with_hook(&self, inputs: &mut Environment, module: Option<&Module>, hook: Option<StatementHook>) -> Result<StatementEval, EvalError> {
+        match self {
+            Declaration::Function(_name, _args, body) | Declaration::ConstFunction(_name, _args, body) => {
                 // When evaluating a function, we must 
                 // `body` is the compound statement of the function
-                body.eval(inputs, module)
+                match body.eval_with_hook(inputs, module, hook)? {
+                    StatementEval::Return(result) => Ok(StatementEval::Return(result)),
+                    // A function that completes without `return` evaluates to nothing
+                    _ => Ok(StatementEval::None)
+                }
             }
+            Declaration::Const(_name, value) => Ok(StatementEval::Return(value.eval(inputs, module)?)),
+            Declaration::Struct(..) => Ok(StatementEval::None),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::rc::Rc;
+
     use crate::ast::expression::Value;
     use crate::ast::expression::Value::IntValue;
     use crate::ast::statement::StatementEval;
 
+    use crate::environment::Environment;
     use crate::parser::Parser;
     use crate::token::tokenize;
 
@@ -46,23 +76,23 @@ mod tests {
         let text = crate::parser::tests::get_simple_file();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         
-        let bar = module.get_function(&"bar".to_string()).unwrap();
-        let result = bar.eval(&mut HashMap::new(), None);
+        let bar = module.get_function("bar".into()).unwrap();
+        let result = bar.eval(&mut Environment::new(), None);
         assert_eq!(Ok(StatementEval::Return(IntValue(3))), result);
         
-        let foo = module.get_function(&"foo".to_string()).unwrap();
-        let result = foo.eval(&mut HashMap::new(), None);
+        let foo = module.get_function("foo".into()).unwrap();
+        let result = foo.eval(&mut Environment::new(), None);
         assert_eq!(Ok(StatementEval::Return(IntValue(5))), result);
         
         // When running the add function without arguments, it's going to fail
-        let add = module.get_function(&"add".to_string()).unwrap();
-        let result = add.eval(&mut HashMap::new(), None);
-        assert!(matches!(result, Err(_)));
+        let add = module.get_function("add".into()).unwrap();
+        let result = add.eval(&mut Environment::new(), None);
+        assert!(result.is_err());
 
         // But we can run the add function with arguments, and it will return the sum of both
-        let mut map = HashMap::new();
+        let mut map = Environment::new();
         map.insert("first".to_string(), IntValue(10));
         map.insert("second".to_string(), IntValue(2));
         let result = add.eval(&mut map, None);
@@ -85,7 +115,7 @@ fn main() {
         ".to_string();
         let tokens = tokenize(&file).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         let result = module.run();
         assert!(result.is_err());
     }
@@ -105,7 +135,7 @@ fn main() {
         ".to_string();
         let tokens = tokenize(&file).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         let result = module.run();
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), StatementEval::Return(IntValue(1)));
@@ -117,9 +147,9 @@ fn main() {
         let text = crate::parser::tests::get_simple_file();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
-        let pass = module.get_function(&"passthrough".to_string()).unwrap();
-        assert!(matches!(pass.eval(&mut HashMap::new(), Some(&module)), Err(_)));
+        let module = parser.parse_module().unwrap();
+        let pass = module.get_function("passthrough".into()).unwrap();
+        assert!(pass.eval(&mut Environment::new(), Some(&module)).is_err());
     }
 
 
@@ -131,11 +161,11 @@ fn recursive(n) {
     return recursive(n - 1);
 }
         ";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
-        let func = module.get_function(&"recursive".to_string()).unwrap();
-        let mut inputs = HashMap::new();
+        let module = parser.parse_module().unwrap();
+        let func = module.get_function("recursive".into()).unwrap();
+        let mut inputs = Environment::new();
         inputs.insert("n".to_string(), Value::IntValue(0));
         assert_eq!(func.eval(&mut inputs, Some(&module)), Ok(StatementEval::Return(IntValue(0))));
 
@@ -157,15 +187,78 @@ fn main() {
     return foo(1) + foo(2);
 }
         ";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         println!("{module:?}");
         let result = module.run();
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(IntValue(6))));
     }
 
+    #[test]
+    fn test_function_without_return() {
+        let text = "\
+fn nothing(n) {
+    a = n;
+}
+
+fn main() {
+    nothing(1);
+    if (nothing(2) == none) {
+        return 1;
+    }
+    return 0;
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(1))));
+
+        let nothing = module.get_function("nothing".into()).unwrap();
+        let mut inputs = Environment::new();
+        inputs.insert("n".to_string(), IntValue(1));
+        assert_eq!(nothing.eval(&mut inputs, Some(&module)), Ok(StatementEval::None));
+    }
+
+    #[test]
+    fn test_return_without_value() {
+        let text = "\
+fn check(n) {
+    if (n < 0) {
+        return;
+    }
+    print(n);
+    return n;
+}
+
+fn main() {
+    return [check(0 - 1), check(2)];
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(Value::List(Rc::new(vec![Value::None, IntValue(2)])))));
+    }
+
+    #[test]
+    fn test_main_without_return() {
+        let text = "fn main() { print(1); }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::None));
+
+        // A `break` outside of a loop also ends the function without a value
+        let text = "fn main() { break; return 1; }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::None));
+    }
+
     #[test]
     fn test_printing() {
         let text = "\
@@ -180,9 +273,9 @@ fn main() {
     return 0;
 }
         ";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         let result = module.run();
         assert_eq!(result, Ok(StatementEval::Return(IntValue(0))));
     }
@@ -1,15 +1,20 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
-use crate::ast::declaration::Declaration;
 use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, CompareExpr, ConstExpr, FunctionCall, IdentExpr, List, ListAccess, ParenthesisExpr};
 use crate::ast::expression::Value::{BoolValue, IntValue, StringValue};
-use crate::ast::statement::StatementEval;
+use crate::ast::declaration::{Declaration, FnArg};
+use crate::ast::statement::{Statement, StatementEval};
+use crate::ast::visitor::{walk_expr, Visitor};
+use crate::environment::Environment;
 use crate::error::EvalError;
 use crate::error::EvalError::{Error, MultipleError, UnknownVariable};
 use crate::module::Module;
 use crate::std::Std;
-use crate::token::{Comp, Op};
+use crate::symbol::Symbol;
+use crate::token::{Comp, Logic, Op};
 
 /// A value is the result of an evaluation
 /// It can be None, if there is no value
@@ -18,135 +23,298 @@ pub enum Value {
     IntValue(i64),
     BoolValue(bool),
     StringValue(String),
-    List(Vec<Value>),
+    CharValue(char),
+    /// The elements are shared between the copies of the list, until one of them is modified
+    List(Rc<Vec<Value>>),
+    /// Key-value pairs, in order of insertion
+    Map(Vec<(Value, Value)>),
+    /// A function, referred to by its name
+    Function(Symbol),
+    /// A function defined in a function body, with the variables captured where it was created.
+    /// Anonymous functions have no name, local functions have a name so that they can call themselves.
+    Closure(Option<Symbol>, Vec<FnArg>, Rc<Statement>, BTreeMap<Symbol, Value>),
+    /// Instance of a struct: its name and its fields, in the order of the declaration
+    Struct(Symbol, Vec<(Symbol, Value)>),
     None
 }
 
+impl Value {
+    /// Returns whether the value is considered `true` when used as a condition.
+    /// Zero, `false`, empty strings, empty lists and `None` are falsy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            IntValue(i) => *i != 0,
+            BoolValue(b) => *b,
+            StringValue(s) => !s.is_empty(),
+            Value::CharValue(_) => true,
+            Value::List(values) => !values.is_empty(),
+            Value::Map(entries) => !entries.is_empty(),
+            Value::Function(_) | Value::Closure(..) | Value::Struct(..) => true,
+            Value::None => false
+        }
+    }
+
+    /// Calls the function held by the value with the provided arguments
+    pub fn call(self, args: Vec<Value>, module: Option<&Module>) -> Result<Value, EvalError> {
+        match self {
+            Value::Function(name) => {
+                // Try to call a standard library function
+                if Std::is_in_standard_lib(&name) {
+                    return Std::eval(&name, &args, module)
+                }
+                match module {
+                    Some(module) => module.call_function(name, args),
+                    None => Err(Error("Module not found"))
+                }
+            }
+            Value::Closure(name, arg_names, body, captured) => {
+                if arg_names.len() != args.len() {
+                    return Err(Error("Wrong number of arguments in function call"));
+                }
+                // The arguments shadow the captured variables
+                let mut inputs: Environment = captured.clone().into_iter().collect();
+                if let Some(name) = &name {
+                    inputs.insert(*name, Value::Closure(Some(*name), arg_names.clone(), body.clone(), captured));
+                }
+                for (arg, value) in arg_names.iter().zip(args) {
+                    inputs.insert(arg.0, value);
+                }
+                let mut eval_body = || match body.eval(&mut inputs, module)? {
+                    StatementEval::Return(result) => Ok(result),
+                    _ => Ok(Value::None),
+                };
+                match module {
+                    Some(module) => module.with_call_depth(eval_body),
+                    None => eval_body()
+                }
+            }
+            _ => Err(Error("Only functions can be called"))
+        }
+    }
+
+    /// Returns the arguments of a call to the function held by the value, in the order of its
+    /// parameters: the positional arguments, followed by the keyword arguments matching the
+    /// remaining parameters.
+    fn bind_keywords(&self, args: Vec<Value>, mut keywords: Vec<(Symbol, Value)>, module: Option<&Module>) -> Result<Vec<Value>, EvalError> {
+        let params: &[FnArg] = match self {
+            Value::Closure(_, params, ..) => params,
+            Value::Function(name) if Std::is_in_standard_lib(name) => return Err(Error("Keyword arguments can't be used with the standard library")),
+            Value::Function(name) => match module.and_then(|module| module.get_function(*name)) {
+                Some(Declaration::Function(_, params, _) | Declaration::ConstFunction(_, params, _)) => params,
+                Some(Declaration::Const(..) | Declaration::Struct(..)) | None => return Err(Error("Function not found"))
+            },
+            _ => return Err(Error("Only functions can be called"))
+        };
+        let mut bound = args;
+        let remaining = &params[bound.len().min(params.len())..];
+        // Each keyword must match a distinct parameter, which is not already bound
+        for (i, (keyword, _)) in keywords.iter().enumerate() {
+            if !remaining.iter().any(|param| param.0 == *keyword) || keywords[..i].iter().any(|(other, _)| other == keyword) {
+                return Err(EvalError::UnknownKeywordArgument(keyword.to_string()));
+            }
+        }
+        for param in remaining {
+            match keywords.iter().position(|(keyword, _)| *keyword == param.0) {
+                Some(i) => bound.push(keywords.remove(i).1),
+                None => return Err(Error("Wrong number of arguments in function call"))
+            }
+        }
+        Ok(bound)
+    }
+
+    /// Converts the value to a boolean, for values that can be used in a condition.
+    /// This is the only conversion used by `if`, `while`, `&&`, `||`, `assert` and `filter`:
+    /// booleans are kept, any integer other than zero is `true`, and the other values are errors.
+    /// See `is_truthy` for a conversion that accepts any value.
+    pub fn as_bool(&self) -> Result<bool, EvalError> {
+        match self {
+            IntValue(i) => Ok(*i != 0),
+            BoolValue(b) => Ok(*b),
+            Value::None => Err(Error("'None' can't be casted to bool")),
+            StringValue(_) => Err(Error("String can't be casted to bool")),
+            Value::CharValue(_) => Err(Error("Char can't be casted to bool")),
+            Value::List(_) => Err(Error("List can't be casted to bool")),
+            Value::Map(_) => Err(Error("Map can't be casted to bool")),
+            Value::Function(_) | Value::Closure(..) => Err(Error("Function can't be casted to bool")),
+            Value::Struct(..) => Err(Error("Struct can't be casted to bool"))
+        }
+    }
+
+    /// Name of the type of the value, as written in the documentation of the language
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            IntValue(_) => "int",
+            BoolValue(_) => "bool",
+            StringValue(_) => "string",
+            Value::CharValue(_) => "char",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function(_) | Value::Closure(..) => "function",
+            Value::Struct(..) => "struct",
+            Value::None => "none"
+        }
+    }
+
+    /// Orders two values of the same type: integers, booleans, strings, characters, or lists of comparable values.
+    /// Unlike the derived `Ord`, values of different types are not comparable.
+    pub fn compare(&self, other: &Value) -> Result<Ordering, EvalError> {
+        match (self, other) {
+            (IntValue(a), IntValue(b)) => Ok(a.cmp(b)),
+            (BoolValue(a), BoolValue(b)) => Ok(a.cmp(b)),
+            (StringValue(a), StringValue(b)) => Ok(a.cmp(b)),
+            (Value::CharValue(a), Value::CharValue(b)) => Ok(a.cmp(b)),
+            (Value::List(a), Value::List(b)) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    match a.compare(b)? {
+                        Ordering::Equal => {}
+                        ordering => return Ok(ordering)
+                    }
+                }
+                Ok(a.len().cmp(&b.len()))
+            }
+            _ => Err(EvalError::TypeError(format!("`{}` and `{}` can't be compared", self.type_name(), other.type_name())))
+        }
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             IntValue(i) => write!(f, "{}", i),
             BoolValue(b) =>  write!(f, "{}", b),
             StringValue(s) =>  write!(f, "{}", s),
-            Value::List(values) => write!(f, "{:?}", values),
+            Value::CharValue(c) => write!(f, "{c}"),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+            Value::Function(name) => write!(f, "<fn {name}>"),
+            Value::Closure(Some(name), ..) => write!(f, "<fn {name}>"),
+            Value::Closure(None, ..) => write!(f, "<closure>"),
+            Value::Struct(name, fields) => {
+                write!(f, "{name} {{")?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {field}: {value}")?;
+                }
+                write!(f, " }}")
+            }
             Value::None => write!(f, "")
         }
     }
 }
 
 /// An expression is something that evaluates to something
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Expr {
     ConstExpr(Value),
     NegExpr(Box<Expr>),
     ParenthesisExpr(Box<Expr>),
     BinaryExpr(Box<Expr>, Op, Box<Expr>),
     CompareExpr(Box<Expr>, Comp, Box<Expr>),
-    AssignmentExpr(String, Box<Expr>),
-    IdentExpr(String),
-    FunctionCall(String, Vec<Expr>),
+    /// Chained comparisons `a < b <= c`, which are true when each comparison is true.
+    /// The operands are evaluated once, from left to right, until a comparison is false.
+    ChainedCompare(Box<Expr>, Vec<(Comp, Expr)>),
+    /// `&&` and `||`, the right side is only evaluated when needed
+    LogicalExpr(Box<Expr>, Logic, Box<Expr>),
+    AssignmentExpr(Symbol, Box<Expr>),
+    IdentExpr(Symbol),
+    /// Call with the positional arguments, followed by the keyword arguments `name = value`
+    FunctionCall(Symbol, Vec<Expr>, Vec<(Symbol, Expr)>),
     List(Vec<Expr>),
-    ListAccess(String, Box<Expr>),
+    /// Map literal: `{ key: value, ... }`
+    Map(Vec<(Expr, Expr)>),
+    /// Access to an element of a list (by position) or of a map (by key)
+    ListAccess(Symbol, Box<Expr>),
+    /// Sub-list `list[start:end]`, where both bounds are optional
+    ListSlice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// Anonymous function: `fn(x) { ... }`
+    Closure(Vec<FnArg>, Box<Statement>),
+    /// `loop { ... }` used as an expression, which evaluates to the value of its `break`
+    Loop(Box<Statement>),
+    /// Construction of a struct: `Point { x: 1, y: 2 }`
+    Struct(Symbol, Vec<(Symbol, Expr)>),
+    /// Access to the field of a struct: `point.x`
+    FieldAccess(Box<Expr>, Symbol),
 }
 
 impl Expr {
     /// Evaluates the expression
     /// buf: local variables (at the current scope)
     /// module: current evaluation module
-    pub fn eval(&self, buf: &mut HashMap<String, Value>, module: Option<&Module>) -> Result<Value, EvalError> {
+    pub fn eval(&self, buf: &mut Environment, module: Option<&Module>) -> Result<Value, EvalError> {
         match self {
             ConstExpr(value) => Ok(value.clone()),
-            Expr::NegExpr(expr) => match expr.eval(buf, module) {
-                Ok(IntValue(value)) => Ok(IntValue(-value)),
-                Err(e) => Err(e),
-                _ => Err(EvalError::Error("A negative express only applies to type Int and Float"))
-            }
+            Expr::NegExpr(expr) => Self::eval_neg(expr.eval(buf, module)?),
             ParenthesisExpr(expr) => expr.eval(buf, module),
-            BinaryExpr(l, op, r) => match (l.eval(buf, module), r.eval(buf, module)) {
-                (Ok(IntValue(l)), Ok(IntValue(r))) => Ok(IntValue(match op {
-                    Op::Plus => l + r,
-                    Op::Minus => l - r,
-                    Op::Times => l * r,
-                    Op::Div => l / r,
-                })),
-                (Ok(Value::List(values1)), Ok(Value::List(values2))) => {
-                    if let Op::Plus = op {
-                        let mut new_values = values1.clone();
-                        for v in &values2 {
-                            new_values.push(v.clone());
-                        }
-                        Ok(Value::List(new_values))
-                    } else {
-                        Err(Error("Only addition is supported for list"))
-                    }
-                }
-                (Ok(StringValue(s1)), Ok(StringValue(s2))) => {
-                    if let Op::Plus = op {
-                        Ok(StringValue(s1 + s2.as_str()))
-                    } else {
-                        Err(Error("Only addition is supported for list"))
-                    }
-                }
-                (Err(r), Ok(_)) => Err(r),
-                (Ok(_), Err(err)) => Err(err),
-                (Err(err1), Err(err2)) => Err(MultipleError(vec![Box::new(err1), Box::new(err2)])),
-                _ => Err(Error("Binary operation not supported"))
-            }
+            BinaryExpr(l, op, r) => Self::eval_binary(l.eval(buf, module), op, r.eval(buf, module)),
             CompareExpr(l, cmp, r) => {
                 match (l.eval(buf, module), r.eval(buf, module)) {
-                    (Ok(left), Ok(right)) => Ok(Self::eval_compare_expr(&left, cmp, &right)),
+                    (Ok(left), Ok(right)) => Self::eval_compare_expr(&left, cmp, &right),
                     (Err(r), _) => Err(r),
                     (_, Err(r)) => Err(r),
                 }
             }
+            Expr::ChainedCompare(first, comparisons) => Self::eval_chained_compare(first, comparisons, buf, module),
+            Expr::LogicalExpr(l, logic, r) => {
+                let left = l.eval(buf, module)?.as_bool()?;
+                match (logic, left) {
+                    // Short-circuit: the right side is not evaluated
+                    (Logic::And, false) => Ok(BoolValue(false)),
+                    (Logic::Or, true) => Ok(BoolValue(true)),
+                    _ => Ok(BoolValue(r.eval(buf, module)?.as_bool()?))
+                }
+            }
             AssignmentExpr(name, value) => {
-                let eval = value.eval(buf, module);
-                match eval {
-                    Ok(value) => buf.insert(name.clone(), value.clone()),
-                    _ => None
-                };
+                let value = Self::eval_assigned(*name, value, buf, module)?;
+                buf.assign(*name, value);
                 Ok(Value::None)
             }
-            IdentExpr(name) => match buf.get(name) {
+            IdentExpr(name) => match buf.get(*name) {
                 Some(value) => Ok(value.clone()),
-                None => Err(UnknownVariable(name.clone())),
+                // The name of a function is a value as well
+                None if Std::is_in_standard_lib(name) || module.is_some_and(|m| m.get_function(*name).is_some()) => {
+                    Ok(Value::Function(*name))
+                }
+                None => Err(UnknownVariable(name.to_string())),
             }
-            FunctionCall(name, inputs) => {
-                // Try to parse a standard library function
-                if Std::is_in_standard_lib(&name) {
-                    let mut evaluated_inputs = vec![];
-                    for input in inputs {
-                        if let Ok(value) = input.eval(buf, module) {
-                            evaluated_inputs.push(value)
-                        }
-                    }
-                    return Std::eval(&name, &evaluated_inputs)
+            FunctionCall(name, inputs, keywords) => {
+                let mut evaluated_inputs = vec![];
+                for input in inputs {
+                    evaluated_inputs.push(input.eval(buf, module)?);
                 }
-
-                if module.is_none() {
-                    return Err(Error("Module not found"))
+                let mut evaluated_keywords = vec![];
+                for (keyword, input) in keywords {
+                    evaluated_keywords.push((*keyword, input.eval(buf, module)?));
                 }
 
-                if let Some(Declaration::Function(_, args, function_body)) =  module.unwrap().get_function(name) {
-                    // We don't provide the function call with all the variables, but just with the provided arguments
-                    // i. evaluate the inputs
-                    let mut function_inputs = HashMap::new();
-                    for i in 0..args.len() {
-                        let arg_name = &args[i];
-                        let arg_expr = &inputs[i];
-                        if let Ok(value) = arg_expr.eval(buf, module) {
-                            function_inputs.insert(arg_name.0.clone(), value);
-                        }
-                    }
-
-                    match function_body.eval(&mut function_inputs, module) {
-                        Ok(StatementEval::Return(result)) => Ok(result),
-                        Err(err) => Err(err),
-                        _ => Ok(Value::None),
-                    }
-                } else {
-                    Err(Error("Function not found"))
+                // The function is either held by a variable, or it is referred to by its name
+                let callee = match buf.get(*name) {
+                    Some(callee @ (Value::Function(_) | Value::Closure(..))) => callee.clone(),
+                    _ => Value::Function(*name)
+                };
+                if !evaluated_keywords.is_empty() {
+                    evaluated_inputs = callee.bind_keywords(evaluated_inputs, evaluated_keywords, module)?;
                 }
+                callee.call(evaluated_inputs, module)
             }
             List(values) => {
                 let mut to_return  = vec![];
@@ -156,64 +324,298 @@ impl Expr {
                         Err(err) => return Err(err)
                     }
                 }
-                Ok(Value::List(to_return))
+                Ok(Value::List(Rc::new(to_return)))
             }
-            ListAccess(name, index) => {
-                // Find the index where to look up
-                let pos = match index.eval(buf, module) {
-                    Ok(IntValue(pos)) => {
-                        pos as usize
+            Expr::Map(entries) => {
+                let mut to_return: Vec<(Value, Value)> = vec![];
+                for (key, value) in entries {
+                    let key = key.eval(buf, module)?;
+                    let value = value.eval(buf, module)?;
+                    // When a key is repeated, the last value is kept
+                    match to_return.iter_mut().find(|(k, _)| *k == key) {
+                        Some(entry) => entry.1 = value,
+                        None => to_return.push((key, value)),
                     }
-                    Err(err) => return Err(err),
-                    _ => return Err(EvalError::Error("When accessing a list, the index must be of type int"))
-                };
-                
+                }
+                Ok(Value::Map(to_return))
+            }
+            ListAccess(name, index) => {
+                let index = index.eval(buf, module)?;
+
                 // Find the value at this index
-                match buf.get(name) {
+                match buf.get(*name) {
                     Some(value) => {
                         match value {
                             Value::List(values) => {
-                                let n = values.len();
-                                Ok(values[pos].clone())
+                                let pos = match index {
+                                    IntValue(pos) => pos,
+                                    _ => return Err(EvalError::Error("When accessing a list, the index must be of type int"))
+                                };
+                                match usize::try_from(pos).ok().and_then(|pos| values.get(pos)) {
+                                    Some(value) => Ok(value.clone()),
+                                    None => Err(EvalError::Error("Index out of range"))
+                                }
                             }
-                            _ => Err(EvalError::Error("Only list can be accessed"))
+                            Value::Map(entries) => match entries.iter().find(|(key, _)| *key == index) {
+                                Some((_, value)) => Ok(value.clone()),
+                                None => Err(EvalError::Error("Key not found in map"))
+                            }
+                            // A string is accessed by the position of its characters
+                            StringValue(s) => {
+                                let pos = match index {
+                                    IntValue(pos) => pos,
+                                    _ => return Err(EvalError::Error("When accessing a string, the index must be of type int"))
+                                };
+                                match usize::try_from(pos).ok().and_then(|pos| s.chars().nth(pos)) {
+                                    Some(c) => Ok(Value::CharValue(c)),
+                                    None => Err(EvalError::Error("Index out of range"))
+                                }
+                            }
+                            _ => Err(EvalError::Error("Only list, map and string can be accessed"))
                         }
                     }
-                    None => Err(EvalError::UnknownVariable(name.clone()))
+                    None => Err(EvalError::UnknownVariable(name.to_string()))
+                }
+            }
+            Expr::ListSlice(list, start, end) => {
+                let values = match list.eval(buf, module)? {
+                    Value::List(values) => values,
+                    _ => return Err(EvalError::Error("Only list can be sliced"))
+                };
+                let start = Self::eval_slice_bound(start, 0, buf, module)?;
+                let end = Self::eval_slice_bound(end, values.len() as i64, buf, module)?;
+                if start < 0 || end < start || end > values.len() as i64 {
+                    return Err(EvalError::Error("Index out of range"));
+                }
+                Ok(Value::List(Rc::new(values[start as usize..end as usize].to_vec())))
+            }
+            // The closure captures a copy of the current variables
+            Expr::Closure(args, body) => Ok(Value::Closure(
+                None,
+                args.clone(),
+                Rc::new(body.as_ref().clone()),
+                buf.variables().into_iter().collect(),
+            )),
+            Expr::Loop(body) => Self::eval_loop(body, buf, module),
+            Expr::Struct(name, fields) => Self::eval_struct(*name, fields, buf, module),
+            Expr::FieldAccess(value, field) => match value.eval(buf, module)? {
+                Value::Struct(_, fields) => match fields.into_iter().find(|(name, _)| name == field) {
+                    Some((_, value)) => Ok(value),
+                    None => Err(EvalError::UnknownField(field.to_string()))
                 }
+                value => Err(EvalError::TypeError(format!("`{}` has no field `{field}`", value.type_name())))
             }
         }
     }
 
-    fn eval_compare_expr(left: &Value, op: &Comp, right: &Value) -> Value {
-        match op {
-            Comp::Equal => BoolValue(left == right),
-            Comp::Lower => BoolValue(left < right),
-            Comp::LowerEq => BoolValue(left <= right),
-            Comp::Higher => BoolValue(left > right),
-            Comp::HigherEq => BoolValue(left >= right)
+    /// Evaluates each comparison of a chain, with the right operand of a comparison used as the
+    /// left operand of the next one
+    fn eval_chained_compare(first: &Expr, comparisons: &[(Comp, Expr)], buf: &mut Environment, module: Option<&Module>) -> Result<Value, EvalError> {
+        let mut left = first.eval(buf, module)?;
+        for (cmp, right) in comparisons {
+            let right = right.eval(buf, module)?;
+            if Self::eval_compare_expr(&left, cmp, &right)? == BoolValue(false) {
+                return Ok(BoolValue(false));
+            }
+            left = right;
+        }
+        Ok(BoolValue(true))
+    }
+
+    /// Evaluates a loop expression to the value of the `break` that stops it, or to `None`
+    /// when the `break` has no value. A `return` leaves the enclosing function, see `EvalError::Return`.
+    fn eval_loop(body: &Statement, buf: &mut Environment, module: Option<&Module>) -> Result<Value, EvalError> {
+        match Statement::eval_loop(body, buf, module)? {
+            StatementEval::Break(value) => Ok(value.unwrap_or(Value::None)),
+            StatementEval::Return(result) => Err(EvalError::Return(result)),
+            StatementEval::None => Ok(Value::None),
+        }
+    }
+
+    /// Evaluates the construction of a struct declared in the module, which must provide
+    /// each of its fields exactly once
+    fn eval_struct(name: Symbol, fields: &[(Symbol, Expr)], buf: &mut Environment, module: Option<&Module>) -> Result<Value, EvalError> {
+        let declared = match module.and_then(|module| module.get_struct(name)) {
+            Some(declared) => declared,
+            None => return Err(Error("Struct not found"))
+        };
+        let mut values = vec![None; declared.len()];
+        for (field, value) in fields {
+            match declared.iter().position(|declared| declared == field) {
+                Some(i) if values[i].is_none() => values[i] = Some(value.eval(buf, module)?),
+                _ => return Err(EvalError::UnknownField(field.to_string()))
+            }
+        }
+        let values = declared.iter().zip(values)
+            .map(|(field, value)| value.map(|value| (*field, value)))
+            .collect::<Option<_>>()
+            .ok_or(Error("Missing field in struct construction"))?;
+        Ok(Value::Struct(name, values))
+    }
+
+    /// Evaluates a bound of a slice, which is replaced by `default` when omitted
+    fn eval_slice_bound(bound: &Option<Box<Expr>>, default: i64, buf: &mut Environment, module: Option<&Module>) -> Result<i64, EvalError> {
+        match bound {
+            None => Ok(default),
+            Some(bound) => match bound.eval(buf, module)? {
+                IntValue(bound) => Ok(bound),
+                _ => Err(EvalError::Error("The bounds of a slice must be of type int"))
+            }
+        }
+    }
+
+    /// Evaluates the value assigned to a variable.
+    /// In `a = a + [x]`, the variable is released before the elements are appended to its list,
+    /// so that they are not copied when the list is not shared with another variable.
+    fn eval_assigned(name: Symbol, value: &Expr, buf: &mut Environment, module: Option<&Module>) -> Result<Value, EvalError> {
+        match value {
+            BinaryExpr(l, op, r) => {
+                let (left, right) = (l.eval(buf, module), r.eval(buf, module));
+                // The concatenation of two lists can't fail, so the variable is never lost
+                if let (IdentExpr(l), Op::Plus, Ok(Value::List(_)), Ok(Value::List(_))) = (l.as_ref(), op, &left, &right) {
+                    if *l == name {
+                        buf.assign(name, Value::None);
+                    }
+                }
+                Self::eval_binary(left, op, right)
+            }
+            value => value.eval(buf, module)
+        }
+    }
+
+    /// Evaluates an arithmetic operation, once both operands are evaluated
+    fn eval_binary(l: Result<Value, EvalError>, op: &Op, r: Result<Value, EvalError>) -> Result<Value, EvalError> {
+        match (l, r) {
+            (Ok(IntValue(l)), Ok(IntValue(r))) => Self::eval_int_binary(l, op, r),
+            (Ok(Value::List(mut values1)), Ok(Value::List(values2))) => {
+                if let Op::Plus = op {
+                    // The elements of the left list are only copied when it is shared
+                    Rc::make_mut(&mut values1).extend(values2.iter().cloned());
+                    Ok(Value::List(values1))
+                } else {
+                    Err(Error("Only addition is supported for list"))
+                }
+            }
+            (Ok(StringValue(s1)), Ok(StringValue(s2))) => {
+                if let Op::Plus = op {
+                    Ok(StringValue(s1 + s2.as_str()))
+                } else {
+                    Err(Error("Only addition is supported for list"))
+                }
+            }
+            (Err(r), Ok(_)) => Err(r),
+            (Ok(_), Err(err)) => Err(err),
+            (Err(err1), Err(err2)) => Err(MultipleError(vec![err1, err2])),
+            _ => Err(Error("Binary operation not supported"))
+        }
+    }
+
+    /// Negates an integer, or each element of a list
+    fn eval_neg(value: Value) -> Result<Value, EvalError> {
+        match value {
+            IntValue(value) => value.checked_neg().map(IntValue).ok_or(EvalError::IntegerOverflow),
+            Value::List(values) => values.iter().cloned().map(Self::eval_neg).collect::<Result<_, _>>().map(|values| Value::List(Rc::new(values))),
+            _ => Err(EvalError::Error("A negative expression only applies to type Int and List"))
+        }
+    }
+
+    /// Evaluates an arithmetic operation on integers, which fails instead of overflowing
+    fn eval_int_binary(l: i64, op: &Op, r: i64) -> Result<Value, EvalError> {
+        let result = match op {
+            Op::Plus => l.checked_add(r),
+            Op::Minus => l.checked_sub(r),
+            Op::Times => l.checked_mul(r),
+            Op::Div if r == 0 => return Err(Error("Division by zero")),
+            Op::Div => l.checked_div(r),
+            Op::Pow => return Self::eval_pow(l, r),
+        };
+        result.map(IntValue).ok_or(EvalError::IntegerOverflow)
+    }
+
+    /// Integer power, `exponent` must be positive
+    fn eval_pow(base: i64, exponent: i64) -> Result<Value, EvalError> {
+        if exponent < 0 {
+            return Err(Error("The exponent of a power must be positive"));
+        }
+        match u32::try_from(exponent).ok().and_then(|exponent| base.checked_pow(exponent)) {
+            Some(result) => Ok(IntValue(result)),
+            None => Err(EvalError::IntegerOverflow),
+        }
+    }
+
+    /// Any two values can be tested for equality: values of different types are never equal.
+    /// The other comparisons follow [`Value::compare`]: values of different types can't be
+    /// ordered, and lists are ordered element by element, a prefix being lower than the whole list.
+    pub(crate) fn eval_compare_expr(left: &Value, op: &Comp, right: &Value) -> Result<Value, EvalError> {
+        if let Comp::Equal = op {
+            return Ok(BoolValue(left == right));
         }
+        let ordering = left.compare(right)?;
+        Ok(BoolValue(match op {
+            Comp::Equal => ordering.is_eq(),
+            Comp::Lower => ordering.is_lt(),
+            Comp::LowerEq => ordering.is_le(),
+            Comp::Higher => ordering.is_gt(),
+            Comp::HigherEq => ordering.is_ge()
+        }))
     }
 
+    /// Returns the names of the variables read by the expression.
+    /// Assigned variables and called functions are not included, and the body of a
+    /// closure contributes the variables it reads, except its own arguments.
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut variables = ReadVariables::default();
+        variables.visit_expr(self);
+        variables.0
+    }
+}
+
+/// Collects the identifiers read by an expression
+#[derive(Default)]
+struct ReadVariables(BTreeSet<String>);
+
+impl Visitor for ReadVariables {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            IdentExpr(name) => {
+                self.0.insert(name.to_string());
+            }
+            ListAccess(name, index) => {
+                self.0.insert(name.to_string());
+                self.visit_expr(index);
+            }
+            Expr::Closure(args, body) => {
+                let mut inner = ReadVariables::default();
+                inner.visit_statement(body);
+                for arg in args {
+                    inner.0.remove(arg.0.as_str());
+                }
+                self.0.append(&mut inner.0);
+            }
+            _ => walk_expr(self, expr)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::rc::Rc;
 
     use crate::ast::expression::{Expr, Value};
     use crate::ast::expression::Expr::ConstExpr;
-    use crate::ast::expression::Value::{BoolValue, IntValue, List, StringValue};
+    use crate::ast::expression::Value::{BoolValue, IntValue, List, Map, StringValue};
     use crate::ast::statement::StatementEval;
+    use crate::environment::Environment;
     use crate::error::EvalError;
     use crate::parser::Parser;
+    use crate::symbol::Symbol;
     use crate::token::tokenize;
 
     fn assert_expression_evaluation(text: &str, expected: Result<Value, EvalError>) {
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
-        let result = ast.eval(&mut HashMap::new(), None);
+        let result = ast.eval(&mut Environment::new(), None);
         assert_eq!(result, expected);
     }
     
@@ -235,6 +637,64 @@ mod tests {
         
     }
     
+    #[test]
+    fn test_std_function_call_eval() {
+        assert_expression_evaluation("len([1,2,3])", Ok(IntValue(3)));
+        assert_expression_evaluation("len([1,2,3]) + len([])", Ok(IntValue(3)));
+        assert_expression_evaluation("print(1, 2)", Ok(Value::None));
+        // Other functions are looked up in the module
+        assert_expression_evaluation("foo(1)", Err(EvalError::Error("Module not found")));
+    }
+
+    #[test]
+    fn test_list_comparison_eval() {
+        assert_expression_evaluation("[1, 2] == [1, 2]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[1, 2] == [2, 1]", Ok(BoolValue(false)));
+        assert_expression_evaluation("[1, [2]] == [1, [2]]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[1, 2] < [1, 3]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[2] > [1, 5]", Ok(BoolValue(true)));
+        // A prefix is lower than the whole list
+        assert_expression_evaluation("[1] < [1, 0]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[] < [1]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[1, 2] <= [1, 2]", Ok(BoolValue(true)));
+        assert_expression_evaluation("[[1], 2] >= [[1], 3]", Ok(BoolValue(false)));
+
+        // Values of different types are never equal, but they can't be ordered
+        assert_expression_evaluation("1 == [1]", Ok(BoolValue(false)));
+        let type_error = |message: &str| Err(EvalError::TypeError(message.to_string()));
+        assert_expression_evaluation("1 < [1]", type_error("`int` and `list` can't be compared"));
+        assert_expression_evaluation("true < 1", type_error("`bool` and `int` can't be compared"));
+        assert_expression_evaluation("[1, 2] < [1, \"a\"]", type_error("`int` and `string` can't be compared"));
+        assert_expression_evaluation("none >= none", type_error("`none` and `none` can't be compared"));
+        // The elements after the first difference are not compared
+        assert_expression_evaluation("[1, 2] < [2, \"a\"]", Ok(BoolValue(true)));
+    }
+
+    #[test]
+    fn test_as_bool() {
+        assert_eq!(IntValue(0).as_bool(), Ok(false));
+        assert_eq!(IntValue(-2).as_bool(), Ok(true));
+        assert_eq!(BoolValue(true).as_bool(), Ok(true));
+        assert_eq!(BoolValue(false).as_bool(), Ok(false));
+        assert_eq!(Value::None.as_bool(), Err(EvalError::Error("'None' can't be casted to bool")));
+        assert_eq!(StringValue("true".to_string()).as_bool(), Err(EvalError::Error("String can't be casted to bool")));
+        assert_eq!(List(Rc::new(vec![IntValue(1)])).as_bool(), Err(EvalError::Error("List can't be casted to bool")));
+        assert_eq!(Map(vec![]).as_bool(), Err(EvalError::Error("Map can't be casted to bool")));
+        assert_eq!(Value::Function("len".into()).as_bool(), Err(EvalError::Error("Function can't be casted to bool")));
+
+        // The same rules apply to every condition
+        assert_expression_evaluation("none && true", Err(EvalError::Error("'None' can't be casted to bool")));
+        assert_expression_evaluation("false || [1]", Err(EvalError::Error("List can't be casted to bool")));
+        assert_expression_evaluation("1 && 2", Ok(BoolValue(true)));
+    }
+
+    #[test]
+    fn test_none_eval() {
+        assert_expression_evaluation("none", Ok(Value::None));
+        assert_expression_evaluation("none == none", Ok(BoolValue(true)));
+        assert_expression_evaluation("1 == none", Ok(BoolValue(false)));
+    }
+
     #[test]
     fn test_bool_eval() {
         assert_expression_evaluation("1 + 1 == 2", Ok(BoolValue(true)));
@@ -244,15 +704,170 @@ mod tests {
         assert_expression_evaluation("(1 + 1) * 2 + 2 == 6", Ok(BoolValue(true)));
     }
     
+    #[test]
+    fn test_arithmetic_errors() {
+        assert_expression_evaluation("1 / 0", Err(EvalError::Error("Division by zero")));
+        assert_expression_evaluation("9223372036854775807 + 1", Err(EvalError::IntegerOverflow));
+        assert_expression_evaluation("-9223372036854775807 - 2", Err(EvalError::IntegerOverflow));
+        assert_expression_evaluation("4611686018427387904 * 2", Err(EvalError::IntegerOverflow));
+        assert_expression_evaluation("(-9223372036854775807 - 1) / -1", Err(EvalError::IntegerOverflow));
+        assert_expression_evaluation("9223372036854775807 + 0", Ok(IntValue(i64::MAX)));
+    }
+
+    #[test]
+    fn test_power_eval() {
+        assert_expression_evaluation("2 ** 10 == 1024", Ok(BoolValue(true)));
+        assert_expression_evaluation("2 ** 10", Ok(IntValue(1024)));
+        assert_expression_evaluation("2 ** 3 ** 2", Ok(IntValue(512)));
+        assert_expression_evaluation("2 * 3 ** 2", Ok(IntValue(18)));
+        assert_expression_evaluation("5 ** 0", Ok(IntValue(1)));
+        assert_expression_evaluation("2 ** 63", Err(EvalError::IntegerOverflow));
+        assert!(matches!(
+            tokenize("2 ** (0 - 1)").map(|tokens| Parser::new(&tokens).parse_expression().unwrap().eval(&mut Environment::new(), None)),
+            Ok(Err(EvalError::Error(_)))
+        ));
+    }
+
+    #[test]
+    fn test_neg_eval() {
+        assert_expression_evaluation("-(1 + 2)", Ok(IntValue(-3)));
+        assert_expression_evaluation("-[1, -2, 3]", Ok(List(Rc::new(vec![IntValue(-1), IntValue(2), IntValue(-3)]))));
+        assert_expression_evaluation("-[1, -2, 3] == [-1, 2, -3]", Ok(BoolValue(true)));
+        assert_expression_evaluation("-[[1], []]", Ok(List(Rc::new(vec![List(Rc::new(vec![IntValue(-1)])), List(Rc::new(vec![]))]))));
+        assert!(matches!(
+            tokenize("-[1, true]").map(|tokens| Parser::new(&tokens).parse_expression().unwrap().eval(&mut Environment::new(), None)),
+            Ok(Err(EvalError::Error(_)))
+        ));
+    }
+
+    #[test]
+    fn test_logical_eval() {
+        assert_expression_evaluation("true && true", Ok(BoolValue(true)));
+        assert_expression_evaluation("true && false", Ok(BoolValue(false)));
+        assert_expression_evaluation("false || true", Ok(BoolValue(true)));
+        assert_expression_evaluation("false || false", Ok(BoolValue(false)));
+        assert_expression_evaluation("1 < 2 && 2 < 3", Ok(BoolValue(true)));
+        assert_expression_evaluation("1 > 2 || 2 > 3", Ok(BoolValue(false)));
+        // `&&` has a higher precedence than `||`
+        assert_expression_evaluation("true || false && false", Ok(BoolValue(true)));
+        assert_expression_evaluation("1 && 0", Ok(BoolValue(false)));
+        assert_expression_evaluation("[1] && true", Err(EvalError::Error("List can't be casted to bool")));
+    }
+
+    #[test]
+    fn test_variables() {
+        let variables = |text: &str| {
+            let tokens = tokenize(text).unwrap();
+            let ast = Parser::new(&tokens).parse_expression().unwrap();
+            ast.variables().into_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(variables("a + foo(b, c)"), vec!["a", "b", "c"]);
+        assert_eq!(variables("x = y[i] + y[0]"), vec!["i", "y"]);
+        assert_eq!(variables("[a, {b: c}][0].d"), vec!["a", "b", "c"]);
+        assert_eq!(variables("fn(x) { return x + n; }"), vec!["n"]);
+        assert!(variables("1 + 2").is_empty());
+    }
+
+    #[test]
+    fn test_chained_comparison_eval() {
+        assert_expression_evaluation("1 < 5 < 10", Ok(BoolValue(true)));
+        assert_expression_evaluation("1 < 15 < 10", Ok(BoolValue(false)));
+        assert_expression_evaluation("1 < 0 < 10", Ok(BoolValue(false)));
+        assert_expression_evaluation("1 <= 1 < 2 == 2", Ok(BoolValue(true)));
+    }
+
+    #[test]
+    fn test_closure_eval() {
+        let text = "\
+fn main() {
+    offset = 10;
+    add = fn(x) { return x + offset; };
+    // The closure captured the value of `offset` when it was created
+    offset = 0;
+    doubled = map(fn(x) { return 2 * x; }, [1, 2]);
+    return add(1) + doubled[1];
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(15))));
+
+        assert_expression_evaluation("fn(x) { return x; } == none", Ok(BoolValue(false)));
+    }
+
+    #[test]
+    fn test_keyword_arguments_eval() {
+        let text = "\
+fn sub(a, b) {
+    return a - b;
+}
+
+fn main() {
+    prefix = fn(p, s) { return p + s; };
+    return [sub(b = 2, a = 10), sub(10, b = 3), prefix(s = \"b\", p = \"a\")];
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(List(Rc::new(vec![IntValue(8), IntValue(7), StringValue("ab".to_string())])))));
+
+        let call = |args: &str| {
+            let tokens = tokenize(&format!("sub({args})")).unwrap();
+            Parser::new(&tokens).parse_expression().unwrap().eval(&mut Environment::new(), Some(&module))
+        };
+        assert_eq!(call("a = 1, c = 2"), Err(EvalError::UnknownKeywordArgument("c".to_string())));
+        // A parameter can't be bound twice
+        assert_eq!(call("1, a = 2, b = 3"), Err(EvalError::UnknownKeywordArgument("a".to_string())));
+        assert_eq!(call("a = 1, a = 2"), Err(EvalError::UnknownKeywordArgument("a".to_string())));
+        assert!(call("a = 1").is_err());
+        assert_expression_evaluation("len(x = [1])", Err(EvalError::Error("Keyword arguments can't be used with the standard library")));
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        // `explode` fails when it is called, so it is only called when the right side is evaluated
+        let text = "\
+fn explode() {
+    return not_defined;
+}
+
+fn main() {
+    if (false && explode()) {
+        return 1;
+    }
+    if (true || explode()) {
+        return false || explode();
+    }
+    return 2;
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Err(EvalError::WithBacktrace {
+            inner: Box::new(EvalError::UnknownVariable("not_defined".to_string())),
+            stack: vec!["main".to_string(), "explode".to_string()],
+        }));
+
+        let tokens = tokenize("false && explode()").unwrap();
+        let ast = Parser::new(&tokens).parse_expression().unwrap();
+        assert_eq!(ast.eval(&mut Environment::new(), Some(&module)), Ok(BoolValue(false)));
+
+        let tokens = tokenize("true || explode()").unwrap();
+        let ast = Parser::new(&tokens).parse_expression().unwrap();
+        assert_eq!(ast.eval(&mut Environment::new(), Some(&module)), Ok(BoolValue(true)));
+    }
+
     #[test]
     fn test_list_eval() {
         let text = "[1,2,3]";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
-        let result = ast.eval(&mut HashMap::new(), None);
+        let result = ast.eval(&mut Environment::new(), None);
         println!("{result:?}");
-        assert_eq!(result, Ok(List(vec![IntValue(1), IntValue(2), IntValue(3)])))
+        assert_eq!(result, Ok(List(Rc::new(vec![IntValue(1), IntValue(2), IntValue(3)]))))
     }
 
     #[test]
@@ -261,12 +876,11 @@ mod tests {
             let text = format!("my_list[{at}]");
             let tokens = tokenize(&text.to_string()).unwrap();
             let mut parser = Parser::new(&tokens);
-            let ast = parser.parse_expression().unwrap();
-            ast
+            parser.parse_expression().unwrap()
         }
         
-        let mut data = HashMap::new();
-        let my_list = List(vec![IntValue(1), IntValue(2), IntValue(3)]);
+        let mut data = Environment::new();
+        let my_list = List(Rc::new(vec![IntValue(1), IntValue(2), IntValue(3)]));
         data.insert("my_list".to_string(), my_list);
         
         assert_eq!(Ok(IntValue(1)), get_list_access_ast(0).eval(&mut data, None));
@@ -274,6 +888,37 @@ mod tests {
         assert_eq!(Ok(IntValue(3)), get_list_access_ast(2).eval(&mut data, None));
     }
 
+    #[test]
+    fn test_list_access_out_of_range() {
+        let mut data = Environment::new();
+        data.insert("my_list".to_string(), List(Rc::new(vec![IntValue(1)])));
+        for text in ["my_list[1]", "my_list[-1]"] {
+            let tokens = tokenize(text).unwrap();
+            let ast = Parser::new(&tokens).parse_expression().unwrap();
+            assert_eq!(ast.eval(&mut data, None), Err(EvalError::Error("Index out of range")));
+        }
+    }
+
+    #[test]
+    fn test_list_slice_eval() {
+        let list = |values: &[i64]| Ok(List(Rc::new(values.iter().map(|v| IntValue(*v)).collect())));
+        assert_expression_evaluation("[0,1,2,3][1:3]", list(&[1, 2]));
+        assert_expression_evaluation("[0,1,2,3][2:]", list(&[2, 3]));
+        assert_expression_evaluation("[0,1,2,3][:2]", list(&[0, 1]));
+        assert_expression_evaluation("[0,1,2,3][:]", list(&[0, 1, 2, 3]));
+        assert_expression_evaluation("[0,1,2,3][1+1:4]", list(&[2, 3]));
+        assert_expression_evaluation("[0,1,2,3][2:2]", list(&[]));
+        assert_expression_evaluation("[0,1,2,3][2:5]", Err(EvalError::Error("Index out of range")));
+        assert_expression_evaluation("[0,1,2,3][3:2]", Err(EvalError::Error("Index out of range")));
+        assert_expression_evaluation("[0,1,2,3][-1:]", Err(EvalError::Error("Index out of range")));
+
+        let mut data = Environment::new();
+        data.insert("my_list".to_string(), List(Rc::new(vec![IntValue(1), IntValue(2), IntValue(3)])));
+        let tokens = tokenize("my_list[1:]").unwrap();
+        let ast = Parser::new(&tokens).parse_expression().unwrap();
+        assert_eq!(ast.eval(&mut data, None), list(&[2, 3]));
+    }
+
     #[test]
     fn test_sum_of_list() {
         let text = "\
@@ -283,18 +928,71 @@ fn main() {
     return b
 }
         ";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         println!("{module:?}");
         let result = module.run();
         println!("{result:?}");
-        assert_eq!(result, Ok(StatementEval::Return(List(vec![IntValue(3), IntValue(1)]))));
+        assert_eq!(result, Ok(StatementEval::Return(List(Rc::new(vec![IntValue(3), IntValue(1)])))));
+    }
+    #[test]
+    fn test_large_list_concatenation() {
+        let mut data = Environment::new();
+        let strings = |range: std::ops::Range<i64>| List(Rc::new(range.map(|i| StringValue(i.to_string())).collect()));
+        data.insert("a".to_string(), strings(0..100_000));
+        data.insert("b".to_string(), strings(100_000..250_000));
+        let tokens = tokenize("a + b + [none]").unwrap();
+        let ast = Parser::new(&tokens).parse_expression().unwrap();
+        match ast.eval(&mut data, None) {
+            Ok(List(values)) => {
+                assert_eq!(values.len(), 250_001);
+                assert_eq!(values[99_999], StringValue("99999".to_string()));
+                assert_eq!(values[100_000], StringValue("100000".to_string()));
+                assert_eq!(values[250_000], Value::None);
+            }
+            result => panic!("Expected a list, got {result:?}")
+        }
+        // The operands are not modified
+        assert_eq!(data.get("a".into()), Some(&strings(0..100_000)));
+    }
+
+    #[test]
+    fn test_map_eval() {
+        assert_expression_evaluation(
+            "{\"a\": 1, \"b\": 1 + 1}",
+            Ok(Map(vec![(StringValue("a".to_string()), IntValue(1)), (StringValue("b".to_string()), IntValue(2))]))
+        );
+        assert_expression_evaluation("{}", Ok(Map(vec![])));
+        // The last value of a repeated key is kept
+        assert_expression_evaluation("{1: 1, 1: 2}", Ok(Map(vec![(IntValue(1), IntValue(2))])));
+    }
+
+    #[test]
+    fn test_map_access_eval() {
+        let text = "\
+fn main() {
+    m = { \"a\": 1, \"b\": 2 };
+    return m[\"a\"] + m[\"b\"];
+}
+
+fn missing() {
+    m = { \"a\": 1 };
+    return m[\"c\"];
+}
+        ";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(3))));
+        let missing = module.get_function("missing".into()).unwrap();
+        assert_eq!(missing.eval(&mut Environment::new(), Some(&module)), Err(EvalError::Error("Key not found in map")));
     }
+
     #[test]
     fn test_string_value() {
         let text = "\"coucou\"";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let result = parser.parse_expression();
         println!("{result:?}");
@@ -304,12 +1002,30 @@ fn main() {
     #[test]
     fn test_string_addition() {
         let text = "\"I love \" + \"susy\"";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression();
-        let result = ast.unwrap().eval(&mut HashMap::new(), None);
+        let result = ast.unwrap().eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StringValue("I love susy".to_string())))
     }
 
+    #[test]
+    fn test_char_value() {
+        let tokens = tokenize("'a'").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_expression(), Ok(ConstExpr(Value::CharValue('a'))));
+        let tokens = tokenize("'\\n'").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_expression(), Ok(ConstExpr(Value::CharValue('\n'))));
+
+        assert_expression_evaluation("'a' == 'a'", Ok(BoolValue(true)));
+        assert_expression_evaluation("'a' < 'b'", Ok(BoolValue(true)));
+        assert_expression_evaluation("'\\t'", Ok(Value::CharValue('\t')));
+
+        // Accessing a string returns its characters
+        let mut env: Environment = [(Symbol::from("s"), StringValue("héllo".to_string()))].into_iter().collect();
+        let eval = |text: &str, env: &mut Environment| Parser::new(&tokenize(text).unwrap()).parse_expression().unwrap().eval(env, None);
+        assert_eq!(eval("s[1]", &mut env), Ok(Value::CharValue('é')));
+        assert_eq!(eval("s[4] == 'o'", &mut env), Ok(BoolValue(true)));
+        assert_eq!(eval("s[5]", &mut env), Err(EvalError::Error("Index out of range")));
+    }
 }
@@ -0,0 +1,422 @@
+use std::fmt::{Display, Formatter, Result, Write};
+
+use crate::ast::declaration::FnArg;
+use crate::ast::expression::{Expr, Value};
+use crate::ast::statement::{Pattern, Statement};
+use crate::symbol::Symbol;
+use crate::token::{Comp, Logic, Op};
+
+/// Number of spaces of each indentation level
+const INDENT: usize = 4;
+
+/// Renders the expression as source code, that can be parsed back to the same expression
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Expr::ConstExpr(value) => write_literal(f, value),
+            Expr::NegExpr(expr) => {
+                let expr = expr.to_string();
+                // `- -a` is clearer than `--a`, which looks like a decrement
+                let separator = if expr.starts_with('-') { " " } else { "" };
+                write!(f, "-{separator}{expr}")
+            }
+            Expr::ParenthesisExpr(expr) => write!(f, "({expr})"),
+            Expr::BinaryExpr(l, op, r) => write!(f, "{l} {} {r}", op_symbol(op)),
+            Expr::CompareExpr(l, comp, r) => write!(f, "{l} {} {r}", comp_symbol(comp)),
+            Expr::ChainedCompare(first, comparisons) => {
+                write!(f, "{first}")?;
+                for (comp, operand) in comparisons {
+                    write!(f, " {} {operand}", comp_symbol(comp))?;
+                }
+                Ok(())
+            }
+            Expr::LogicalExpr(l, logic, r) => write!(f, "{l} {} {r}", logic_symbol(logic)),
+            Expr::AssignmentExpr(name, value) => write!(f, "{name} = {value}"),
+            Expr::IdentExpr(name) => write!(f, "{name}"),
+            Expr::FunctionCall(name, args, keywords) => {
+                write!(f, "{name}(")?;
+                write_separated(f, args)?;
+                for (i, (keyword, arg)) in keywords.iter().enumerate() {
+                    if i > 0 || !args.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{keyword} = {arg}")?;
+                }
+                write!(f, ")")
+            }
+            Expr::List(values) => {
+                write!(f, "[")?;
+                write_separated(f, values)?;
+                write!(f, "]")
+            }
+            Expr::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+            Expr::ListAccess(name, index) => write!(f, "{name}[{index}]"),
+            Expr::ListSlice(list, start, end) => {
+                write!(f, "{list}[")?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                write!(f, "]")
+            }
+            Expr::Closure(args, body) => {
+                write!(f, "fn")?;
+                write_args(f, args)?;
+                write!(f, " ")?;
+                // There are no line breaks inside an expression
+                write_statement(f, body, None)
+            }
+            Expr::Loop(body) => {
+                write!(f, "loop ")?;
+                write_statement(f, body, None)
+            }
+            Expr::Struct(name, fields) => {
+                write!(f, "{name} {{")?;
+                write_fields(f, fields.iter().map(|(field, value)| (field, value)))
+            }
+            Expr::FieldAccess(value, field) => write!(f, "{value}.{field}"),
+        }
+    }
+}
+
+/// Renders the statement as source code, with blocks indented by 4 spaces
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_statement(f, self, Some(0))
+    }
+}
+
+/// Writes the statement, which starts at the current position and whose inner lines are
+/// indented by `indent` spaces, plus one level for the content of blocks.
+/// Without indentation, the statement is written on a single line.
+fn write_statement(f: &mut Formatter<'_>, statement: &Statement, indent: Option<usize>) -> Result {
+    match statement {
+        Statement::SimpleStatement(expr) => write!(f, "{expr};"),
+        Statement::Return(Expr::ConstExpr(Value::None)) => write!(f, "return;"),
+        Statement::Return(expr) => write!(f, "return {expr};"),
+        Statement::CompoundStatement(statements) => {
+            if statements.is_empty() {
+                return write!(f, "{{}}");
+            }
+            match indent {
+                Some(indent) => {
+                    writeln!(f, "{{")?;
+                    for statement in statements {
+                        write!(f, "{:width$}", "", width = indent + INDENT)?;
+                        write_statement(f, statement, Some(indent + INDENT))?;
+                        writeln!(f)?;
+                    }
+                    write!(f, "{:width$}}}", "", width = indent)
+                }
+                None => {
+                    write!(f, "{{")?;
+                    for statement in statements {
+                        write!(f, " ")?;
+                        write_statement(f, statement, None)?;
+                    }
+                    write!(f, " }}")
+                }
+            }
+        }
+        Statement::If(condition, body, else_statement) => {
+            write!(f, "if ({condition}) ")?;
+            write_statement(f, body, indent)?;
+            if let Some(else_statement) = else_statement {
+                write!(f, " else ")?;
+                write_statement(f, else_statement, indent)?;
+            }
+            Ok(())
+        }
+        Statement::Loop(body) => {
+            write!(f, "loop ")?;
+            write_statement(f, body, indent)
+        }
+        Statement::Repeat(count, body) => {
+            write!(f, "repeat {count} ")?;
+            write_statement(f, body, indent)
+        }
+        Statement::While(condition, body) => {
+            write!(f, "while ({condition}) ")?;
+            write_statement(f, body, indent)
+        }
+        Statement::DoWhile(body, condition) => {
+            write!(f, "do ")?;
+            write_statement(f, body, indent)?;
+            write!(f, " while ({condition});")
+        }
+        Statement::Break(None) => write!(f, "break;"),
+        Statement::Break(Some(value)) => write!(f, "break {value};"),
+        Statement::LocalFn(name, args, body) => {
+            write!(f, "fn {name}")?;
+            write_args(f, args)?;
+            write!(f, " ")?;
+            write_statement(f, body, indent)
+        }
+        Statement::AssertEq(expected, actual) => write!(f, "assert_eq {expected}, {actual};"),
+        Statement::Match(subject, arms) => {
+            write!(f, "match {subject} {{")?;
+            for (pattern, body) in arms {
+                match indent {
+                    Some(indent) => write!(f, "\n{:width$}", "", width = indent + INDENT)?,
+                    None => write!(f, " ")?,
+                }
+                match pattern {
+                    Pattern::Int(i) => write!(f, "{i} => ")?,
+                    Pattern::Wildcard => write!(f, "_ => ")?,
+                }
+                write_statement(f, body, indent.map(|indent| indent + INDENT))?;
+            }
+            match indent {
+                Some(indent) if !arms.is_empty() => write!(f, "\n{:width$}}}", "", width = indent),
+                Some(_) => write!(f, "}}"),
+                None => write!(f, " }}"),
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Renders the value as source code, with each element of a non-empty list or map on
+    /// its own line, indented by `indent` spaces for each level of nesting
+    pub fn pretty(&self, indent: usize) -> String {
+        let mut out = String::new();
+        // Writing to a string can't fail
+        let _ = write_pretty(&mut out, self, indent, 0);
+        out
+    }
+}
+
+/// Writes the value, whose inner lines are indented by `level` times `indent` spaces, plus one level
+fn write_pretty(out: &mut String, value: &Value, indent: usize, level: usize) -> Result {
+    let inner = indent * (level + 1);
+    match value {
+        Value::List(values) if !values.is_empty() => {
+            writeln!(out, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                write!(out, "{:inner$}", "")?;
+                write_pretty(out, value, indent, level + 1)?;
+                writeln!(out, "{}", if i + 1 < values.len() { "," } else { "" })?;
+            }
+            write!(out, "{:width$}]", "", width = indent * level)
+        }
+        Value::Map(entries) if !entries.is_empty() => {
+            writeln!(out, "{{")?;
+            for (i, (key, value)) in entries.iter().enumerate() {
+                write!(out, "{:inner$}", "")?;
+                write_pretty(out, key, indent, level + 1)?;
+                write!(out, ": ")?;
+                write_pretty(out, value, indent, level + 1)?;
+                writeln!(out, "{}", if i + 1 < entries.len() { "," } else { "" })?;
+            }
+            write!(out, "{:width$}}}", "", width = indent * level)
+        }
+        value => write!(out, "{}", Literal(value)),
+    }
+}
+
+/// Displays a value as it is written in the source code
+struct Literal<'a>(&'a Value);
+
+impl Display for Literal<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_literal(f, self.0)
+    }
+}
+
+fn write_separated(f: &mut Formatter<'_>, exprs: &[Expr]) -> Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{expr}")?;
+    }
+    Ok(())
+}
+
+fn write_args(f: &mut Formatter<'_>, args: &[FnArg]) -> Result {
+    let args: Vec<&str> = args.iter().map(|arg| arg.0.as_str()).collect();
+    write!(f, "({})", args.join(", "))
+}
+
+/// Writes a value as it is written in the source code
+fn write_literal(f: &mut Formatter<'_>, value: &Value) -> Result {
+    match value {
+        Value::StringValue(s) => write!(f, "\"{s}\""),
+        Value::CharValue(c) => match c {
+            '\n' => write!(f, "'\\n'"),
+            '\t' => write!(f, "'\\t'"),
+            '\\' | '\'' => write!(f, "'\\{c}'"),
+            c => write!(f, "'{c}'"),
+        },
+        Value::None => write!(f, "none"),
+        Value::List(values) => {
+            write!(f, "[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_literal(f, value)?;
+            }
+            write!(f, "]")
+        }
+        Value::Map(entries) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_literal(f, key)?;
+                write!(f, ": ")?;
+                write_literal(f, value)?;
+            }
+            write!(f, "}}")
+        }
+        Value::Struct(name, fields) => {
+            write!(f, "{name} {{")?;
+            write_fields(f, fields.iter().map(|(field, value)| (field, Literal(value))))
+        }
+        value => write!(f, "{value}"),
+    }
+}
+
+/// Writes the fields of a struct, followed by the closing brace
+fn write_fields<'a, T: Display + 'a>(f: &mut Formatter<'_>, fields: impl Iterator<Item=(&'a Symbol, T)>) -> Result {
+    for (i, (field, value)) in fields.enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, " {field}: {value}")?;
+    }
+    write!(f, " }}")
+}
+
+fn op_symbol(op: &Op) -> &'static str {
+    match op {
+        Op::Plus => "+",
+        Op::Minus => "-",
+        Op::Times => "*",
+        Op::Div => "/",
+        Op::Pow => "**",
+    }
+}
+
+fn comp_symbol(comp: &Comp) -> &'static str {
+    match comp {
+        Comp::Equal => "==",
+        Comp::Lower => "<",
+        Comp::LowerEq => "<=",
+        Comp::Higher => ">",
+        Comp::HigherEq => ">=",
+    }
+}
+
+fn logic_symbol(logic: &Logic) -> &'static str {
+    match logic {
+        Logic::And => "&&",
+        Logic::Or => "||",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::ast::expression::Value;
+    use crate::ast::expression::Value::{IntValue, List, Map, StringValue};
+    use crate::environment::Environment;
+    use crate::parser::{parse_expression, parse_statements};
+    use crate::token::tokenize;
+
+    /// Checks that the expression is printed as written, and parsed back to the same expression
+    fn assert_printed(text: &str) {
+        let ast = parse_expression(&tokenize(text).unwrap()).unwrap();
+        assert_eq!(ast.to_string(), text);
+        assert_eq!(parse_expression(&tokenize(&ast.to_string()).unwrap()).unwrap(), ast);
+    }
+
+    #[test]
+    fn test_print_expressions() {
+        assert_printed("(1 + 2) * 3");
+        assert_printed("1 + 2 * 3 ** 2");
+        assert_printed("-a == b || c < 2 && d");
+        assert_printed("0 <= i < len(a) == true");
+        assert_printed("- -a");
+        assert_printed("a = foo(1, [2, \"three\"], {none: true})");
+        assert_printed("foo(1, b = 2, c = bar(d = 3))");
+        assert_printed("my_list[i + 1]");
+        assert_printed("my_list[1:]");
+        assert_printed("[0, 1][:n]");
+        assert_printed("['a', '\\n', '\\'']");
+        assert_printed("Point { x: 1, y: p.y }");
+    }
+
+    #[test]
+    fn test_pretty_values() {
+        let value = List(Rc::new(vec![IntValue(1), List(Rc::new(vec![StringValue("a".to_string()), List(Rc::new(vec![]))])), Map(vec![(IntValue(2), Value::None)])]));
+        assert_eq!(value.pretty(2), "\
+[
+  1,
+  [
+    \"a\",
+    []
+  ],
+  {
+    2: none
+  }
+]");
+        assert_eq!(value.pretty(0).lines().count(), 10);
+        assert_eq!(IntValue(3).pretty(4), "3");
+        // The result can be parsed back
+        let parsed = parse_expression(&tokenize(&value.pretty(4)).unwrap()).unwrap();
+        assert_eq!(parsed.eval(&mut Environment::new(), None), Ok(value));
+    }
+
+    #[test]
+    fn test_print_statements() {
+        let text = "\
+{
+    i = 0;
+    loop {
+        if (i == 10) {
+            break;
+        } else {
+            i = i + 1;
+        }
+    }
+    repeat 2 {}
+    do {
+        i = i - 1;
+    } while (i > 5);
+    match i {
+        1 => {
+            i = 2;
+        }
+        _ => {}
+    }
+    if (i) {
+        return;
+    }
+    return i;
+}";
+        let statements = parse_statements(&tokenize(text).unwrap()).unwrap();
+        assert_eq!(statements[0].to_string(), text);
+
+        let statements = parse_statements(&tokenize("{fn inc(x) { return x + 1; } f = fn(y) {if (y) {return y;}};}").unwrap()).unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "{\n    fn inc(x) {\n        return x + 1;\n    }\n    f = fn(y) { if (y) { return y; } };\n}"
+        );
+    }
+}
@@ -1,13 +1,15 @@
-use std::collections::HashMap;
-use std::io::read_to_string;
+use std::rc::Rc;
 
+use crate::ast::declaration::FnArg;
 use crate::ast::expression::{Expr, Value};
+use crate::environment::Environment;
 use crate::error::EvalError;
 use crate::error::EvalError::Error;
 use crate::module::Module;
+use crate::symbol::Symbol;
 
 /// A statement is something that does not evaluate to something
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Statement {
     /// A statement of the type `expr;'
     SimpleStatement(Expr),
@@ -16,73 +18,176 @@ pub enum Statement {
     /// A return statement, for functions
     Return(Expr),
     /// If statement
-    /// The else is encapsulated as an optional statement
+    /// The else is encapsulated as an optional statement.
+    /// Both branches are blocks, so the variables they create are not visible after the `if`
     If(Expr, Box<Statement>, Option<Box<Statement>>),
     /// Loops
     Loop(Box<Statement>),
-    /// break is a statement since it does not execute to a value but to a side effect
-    Break
+    /// Loop running its body a fixed number of times: `repeat n { ... }`
+    Repeat(Expr, Box<Statement>),
+    /// Loop running its body as long as the condition is true: `while (cond) { ... }`
+    While(Expr, Box<Statement>),
+    /// Loop checking its condition after each run of its body: `do { ... } while (cond);`.
+    /// The variables created in the body are not visible in the condition.
+    DoWhile(Box<Statement>, Expr),
+    /// break is a statement since it does not execute to a value but to a side effect.
+    /// `break value;` gives its value to the enclosing loop expression
+    Break(Option<Expr>),
+    /// A function declared in a function body, which is only visible in the enclosing block
+    LocalFn(Symbol, Vec<FnArg>, Box<Statement>),
+    /// `assert_eq expected, actual;` fails the evaluation when both values are different
+    AssertEq(Expr, Expr),
+    /// `match expr { 1 => { ... } _ => { ... } }` evaluates the body of the first matching arm
+    Match(Expr, Vec<(Pattern, Statement)>),
 }
 
+/// The pattern of an arm of a `match` statement
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Pattern {
+    Int(i64),
+    /// `_` matches any value
+    Wildcard,
+}
+
+impl Pattern {
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Pattern::Int(i) => *value == Value::IntValue(*i),
+            Pattern::Wildcard => true,
+        }
+    }
+}
+
+/// Called with an expression statement and its value, see `Statement::eval_with_hook`
+pub type StatementHook<'a> = &'a dyn Fn(&Expr, &Value);
+
 #[derive(Debug, PartialEq, Eq)]
 /// Holds the result of a statement's runtime evaluation
 pub enum StatementEval {
     Return(Value),
-    Break,
+    Break(Option<Value>),
     None
 }
 
 impl Statement {
     
-    fn eval_statement_list(inputs: &mut HashMap<String, Value>, module: Option<&Module>, statements: &Vec<Statement>) -> Result<StatementEval, EvalError> {
+    fn eval_statement_list(inputs: &mut Environment, module: Option<&Module>, statements: &Vec<Statement>, hook: Option<StatementHook>) -> Result<StatementEval, EvalError> {
         for stm in statements {
-            match stm.eval(inputs, module) {
+            let result = match (stm, hook) {
+                (Statement::SimpleStatement(expr), Some(hook)) => expr.eval(inputs, module).map(|value| {
+                    hook(expr, &value);
+                    StatementEval::None
+                }),
+                (stm, _) => stm.eval(inputs, module),
+            };
+            match result {
                 Ok(StatementEval::None) => {}
-                Ok(StatementEval::Break) => return Ok(StatementEval::Break),
+                Ok(StatementEval::Break(value)) => return Ok(StatementEval::Break(value)),
                 Ok(StatementEval::Return(result)) => {
                     // If any of the statement returned anything, we return
                     // TODO there is probably a problem here.
                     return Ok(StatementEval::Return(result))
                 }
+                // A `return` in a loop expression of the statement
+                Err(EvalError::Return(result)) => return Ok(StatementEval::Return(result)),
                 Err(err) => return Err(err)
             }
         }
         Ok(StatementEval::None)
     }
+
+    /// Evaluates the statements of a block in a new frame: the variables created in the block
+    /// are dropped when it is left, while the enclosing variables can be updated.
+    /// The hook is called with the value of each expression statement of the block, but not of the nested blocks.
+    fn eval_block(inputs: &mut Environment, module: Option<&Module>, statements: &Vec<Statement>, hook: Option<StatementHook>) -> Result<StatementEval, EvalError> {
+        if let Some(module) = module {
+            module.count_step()?;
+        }
+        inputs.push_frame();
+        let result = Self::eval_statement_list(inputs, module, statements, hook);
+        inputs.pop_frame();
+        result
+    }
     
     
-    pub fn eval(&self, inputs: &mut HashMap<String, Value>, module: Option<&Module>) -> Result<StatementEval, EvalError> {
+    /// Returns whether the evaluation of the statement always ends with a `return` or a `break`,
+    /// so that the statements following it in the same block are never evaluated
+    pub fn always_exits(&self) -> bool {
+        match self {
+            Statement::Return(_) | Statement::Break(_) => true,
+            Statement::CompoundStatement(statements) => statements.iter().any(Statement::always_exits),
+            Statement::If(_, body, Some(else_statement)) => body.always_exits() && else_statement.always_exits(),
+            Statement::Match(_, arms) => {
+                arms.iter().any(|(pattern, _)| *pattern == Pattern::Wildcard)
+                    && arms.iter().all(|(_, body)| body.always_exits())
+            }
+            // A `break` in the body of a loop only leaves the loop
+            _ => false
+        }
+    }
+
+    /// Runs the body of a `loop` until it breaks, which gives `StatementEval::Break` with the value
+    /// of the `break`, or until it returns
+    pub(crate) fn eval_loop(body: &Statement, inputs: &mut Environment, module: Option<&Module>) -> Result<StatementEval, EvalError> {
+        match body {
+            Statement::CompoundStatement(statements) => {
+                // Each iteration has its own frame. An error in the body stops the loop
+                loop {
+                    match Self::eval_block(inputs, module, statements, None)? {
+                        StatementEval::None => {}
+                        exit => return Ok(exit),
+                    }
+                }
+            }
+            _ => Err(Error("A loop statement can only be associated with a compound statement."))
+        }
+    }
+
+    /// Evaluates the value given to the enclosing loop by a `break`
+    fn eval_break(value: Option<&Expr>, inputs: &mut Environment, module: Option<&Module>) -> Result<StatementEval, EvalError> {
+        match value {
+            Some(value) => Ok(StatementEval::Break(Some(value.eval(inputs, module)?))),
+            None => Ok(StatementEval::Break(None)),
+        }
+    }
+
+    /// Evaluates the condition of a loop, which is checked before each iteration
+    fn eval_condition(condition: &Expr, inputs: &mut Environment, module: Option<&Module>) -> Result<bool, EvalError> {
+        match condition {
+            // `while (true)` does not need to be evaluated
+            Expr::ConstExpr(Value::BoolValue(b)) => Ok(*b),
+            condition => condition.eval(inputs, module)?.as_bool()
+        }
+    }
+
+    /// Evaluates the statement like `eval`. When it is a block, the hook is called with the value
+    /// of each of its expression statements.
+    pub fn eval_with_hook(&self, inputs: &mut Environment, module: Option<&Module>, hook: Option<StatementHook>) -> Result<StatementEval, EvalError> {
+        match self {
+            Statement::CompoundStatement(statements) => Self::eval_block(inputs, module, statements, hook),
+            statement => statement.eval(inputs, module),
+        }
+    }
+
+    pub fn eval(&self, inputs: &mut Environment, module: Option<&Module>) -> Result<StatementEval, EvalError> {
         match self {
             Statement::SimpleStatement(expr) => {
                 match expr.eval(inputs, module) {
-                    Ok(_) => return Ok(StatementEval::None),
-                    Err(err) => return Err(err)
+                    Ok(_) => Ok(StatementEval::None),
+                    Err(err) => Err(err)
                 }
             }
             Statement::Return(expr) => {
-                return match expr.eval(inputs, module) {
+                match expr.eval(inputs, module) {
                     Ok(result) => Ok(StatementEval::Return(result)),
                     Err(err) => Err(err)
                 }
             }
-            Statement::CompoundStatement(statements) => {
-                // All the new variables defined in the new scope are bound to remain in the scope
-                // This forbid variable-side effect
-                let mut copied_environment = inputs.clone();
-                Self::eval_statement_list(&mut copied_environment, module, statements)
-            }
+            Statement::CompoundStatement(statements) => Self::eval_block(inputs, module, statements, None),
             Statement::If(condition, body, else_statement)  => {
                 match condition.eval(inputs, module) {
                     Ok(cond) => {
-                        let test = match cond {
-                            Value::IntValue(i) => i != 0,
-                            Value::BoolValue(b) => b,
-                            Value::None => return Err(Error("'None' can't be casted to bool")),
-                            Value::StringValue(_) => return Err(Error("String can't be casted to bool")),
-                            Value::List(_) => return Err(Error("List can't be casted to bool"))
-                        };
-
-                        if test {
+                        if cond.as_bool()? {
                             body.eval(inputs, module)
                         } else if let Some(else_body) = else_statement {
                             else_body.eval(inputs, module)
@@ -93,28 +198,87 @@ impl Statement {
                     Err(err) => Err(err)
                 }
             }
-            Statement::Loop(body) => {
-                // We know that the body is necessary a compound statement
-                // Unfortunately, it is not possible to call `
+            // The value of a `break` is dropped when the loop is used as a statement
+            Statement::Loop(body) => match Self::eval_loop(body, inputs, module)? {
+                StatementEval::Break(_) => Ok(StatementEval::None),
+                exit => Ok(exit),
+            },
+            Statement::Repeat(count, body) => {
+                let count = match count.eval(inputs, module) {
+                    Ok(Value::IntValue(count)) => count,
+                    Err(err) => return Err(err),
+                    _ => return Err(Error("The number of repetitions must be of type int"))
+                };
                 match body.as_ref() {
                     Statement::CompoundStatement(statements) => {
-                        while let Ok(result) = Self::eval_statement_list(inputs, module, statements) {
-                            match result {
-                                StatementEval::Break => {
-                                    return Ok(StatementEval::None)
-                                }
-                                _ => {}
+                        for _ in 0..count {
+                            match Self::eval_block(inputs, module, statements, None)? {
+                                StatementEval::Break(_) => break,
+                                StatementEval::Return(result) => return Ok(StatementEval::Return(result)),
+                                StatementEval::None => {}
                             }
                         }
-                        
+                        Ok(StatementEval::None)
                     }
-                    _ => return Err(Error("A loop statement can only be associated with a compound statement."))
+                    _ => Err(Error("A repeat statement can only be associated with a compound statement."))
                 }
-                
+            }
+            Statement::While(condition, body) => {
+                match body.as_ref() {
+                    Statement::CompoundStatement(statements) => {
+                        while Self::eval_condition(condition, inputs, module)? {
+                            match Self::eval_block(inputs, module, statements, None)? {
+                                StatementEval::Break(_) => break,
+                                StatementEval::Return(result) => return Ok(StatementEval::Return(result)),
+                                StatementEval::None => {}
+                            }
+                        }
+                        Ok(StatementEval::None)
+                    }
+                    _ => Err(Error("A while statement can only be associated with a compound statement."))
+                }
+            }
+            Statement::DoWhile(body, condition) => {
+                match body.as_ref() {
+                    Statement::CompoundStatement(statements) => {
+                        loop {
+                            match Self::eval_block(inputs, module, statements, None)? {
+                                StatementEval::Break(_) => break,
+                                StatementEval::Return(result) => return Ok(StatementEval::Return(result)),
+                                StatementEval::None => {}
+                            }
+                            if !Self::eval_condition(condition, inputs, module)? {
+                                break;
+                            }
+                        }
+                        Ok(StatementEval::None)
+                    }
+                    _ => Err(Error("A do-while statement can only be associated with a compound statement."))
+                }
+            }
+            Statement::Break(value) => Self::eval_break(value.as_ref(), inputs, module),
+            Statement::LocalFn(name, args, body) => {
+                // The function is stored as a variable, so that it follows the scoping rules of variables
+                let captured = inputs.variables().into_iter().collect();
+                let function = Value::Closure(Some(*name), args.clone(), Rc::new(body.as_ref().clone()), captured);
+                inputs.insert(*name, function);
                 Ok(StatementEval::None)
             }
-            Statement::Break => {
-                Ok(StatementEval::Break)
+            Statement::Match(subject, arms) => {
+                let subject = subject.eval(inputs, module)?;
+                match arms.iter().find(|(pattern, _)| pattern.matches(&subject)) {
+                    Some((_, body)) => body.eval(inputs, module),
+                    None => Ok(StatementEval::None)
+                }
+            }
+            Statement::AssertEq(expected, actual) => {
+                let expected = expected.eval(inputs, module)?;
+                let actual = actual.eval(inputs, module)?;
+                if expected == actual {
+                    Ok(StatementEval::None)
+                } else {
+                    Err(EvalError::AssertionFailed(format!("expected {expected}, got {actual}")))
+                }
             }
         }
     }
@@ -122,21 +286,24 @@ impl Statement {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::rc::Rc;
+
     use crate::ast::expression::Value;
-    use crate::ast::statement::StatementEval;
+    use crate::ast::statement::{Statement, StatementEval};
 
+    use crate::environment::Environment;
     use crate::error::EvalError;
     use crate::parser::{parse_statements, Parser};
+    use crate::symbol::Symbol;
     use crate::token::tokenize;
 
     fn assert_statement_eval(text: &str, expected: Result<StatementEval, EvalError>) {
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let statements = parser.parse_statements();
+        let statements = parser.parse_statements().unwrap();
         assert_eq!(1, statements.len());
         let block = &statements[0];
-        let result = block.eval(&mut HashMap::new(), None);
+        let result = block.eval(&mut Environment::new(), None);
         assert_eq!(result, expected);
     }
 
@@ -147,6 +314,98 @@ mod tests {
         assert_statement_eval("{a=1; b=1; return a + b}", Ok(StatementEval::Return(Value::IntValue(2))));
     }
 
+    #[test]
+    fn test_while_eval() {
+        assert_statement_eval("{ i = 0; while (true) { i = i + 1; if (i == 3) { break; } } return i == 3; }", Ok(StatementEval::Return(Value::BoolValue(true))));
+        assert_statement_eval("{ i = 0; while (i < 5) { i = i + 1; } return i; }", Ok(StatementEval::Return(Value::IntValue(5))));
+        assert_statement_eval("{ while (false) { return 1; } return 2; }", Ok(StatementEval::Return(Value::IntValue(2))));
+        // A `return` ends the loop, and the errors of the body are propagated
+        assert_statement_eval("{ while (true) { return 4; } }", Ok(StatementEval::Return(Value::IntValue(4))));
+        assert_statement_eval("{ while (true) { b; } }", Err(EvalError::UnknownVariable("b".to_string())));
+        assert!(matches!(Statement::eval(&parse_statements(&tokenize("while (none) {}").unwrap()).unwrap()[0], &mut Environment::new(), None), Err(EvalError::Error(_))));
+        assert_statement_eval("{ i = 0; while (3 - i) { i = i + 1; } return i; }", Ok(StatementEval::Return(Value::IntValue(3))));
+        assert_statement_eval("if (none) {}", Err(EvalError::Error("'None' can't be casted to bool")));
+    }
+
+    #[test]
+    fn test_do_while_eval() {
+        // The body runs once even though the condition is false
+        assert_statement_eval("{ i = 0; do { i = i + 1; } while (false); return i; }", Ok(StatementEval::Return(Value::IntValue(1))));
+        assert_statement_eval("{ i = 0; do { i = i + 1; } while (i < 5); return i; }", Ok(StatementEval::Return(Value::IntValue(5))));
+        assert_statement_eval("{ i = 0; do { i = i + 1; if (i == 3) { break; } } while (true); return i; }", Ok(StatementEval::Return(Value::IntValue(3))));
+        assert_statement_eval("{ do { return 4; } while (true); }", Ok(StatementEval::Return(Value::IntValue(4))));
+        // The condition is only evaluated after the body
+        assert_statement_eval("{ do { b; } while (c); }", Err(EvalError::UnknownVariable("b".to_string())));
+        assert_statement_eval("{ do { b = 1; } while (b); }", Err(EvalError::UnknownVariable("b".to_string())));
+        assert_statement_eval("do {} while ([]);", Err(EvalError::Error("List can't be casted to bool")));
+    }
+
+    #[test]
+    fn test_increment_eval() {
+        assert_statement_eval("{ i = 0; i++; i++; i--; i++; return i; }", Ok(StatementEval::Return(Value::IntValue(2))));
+        assert_statement_eval("{ i = 0; n = 0; while (i < 5) { n = n + i; i++; } return n; }", Ok(StatementEval::Return(Value::IntValue(10))));
+        assert_statement_eval("{ i++; return i; }", Err(EvalError::UnknownVariable("i".to_string())));
+        // An assignment fails with the error of its value
+        assert_statement_eval("{ x = error(\"boom\"); return 1; }", Err(EvalError::UserError("boom".to_string())));
+        assert_statement_eval("{ x = y; return 1; }", Err(EvalError::UnknownVariable("y".to_string())));
+        assert_statement_eval("{ a = [1]; a = a + 2; return a; }", Err(EvalError::Error("Binary operation not supported")));
+        // `--` followed by an operand still subtracts a negative value
+        assert_statement_eval("{ a = 5; return [1--2, a--1]; }", Ok(StatementEval::Return(Value::List(Rc::new(vec![Value::IntValue(3), Value::IntValue(6)])))));
+    }
+
+    #[test]
+    fn test_break_value_eval() {
+        assert_statement_eval("{ x = loop { break 42; }; return x; }", Ok(StatementEval::Return(Value::IntValue(42))));
+        assert_statement_eval("{ i = 0; x = loop { i = i + 1; if (i == 5) { break i * 2; } }; return x; }", Ok(StatementEval::Return(Value::IntValue(10))));
+        assert_statement_eval("{ x = loop { break; }; return x; }", Ok(StatementEval::Return(Value::None)));
+        // The value only leaves the innermost loop
+        assert_statement_eval("{ x = loop { loop { break 1; } break 2; }; return x; }", Ok(StatementEval::Return(Value::IntValue(2))));
+        assert_statement_eval("{ loop { break 42; } return 0; }", Ok(StatementEval::Return(Value::IntValue(0))));
+        assert_statement_eval("break 42;", Ok(StatementEval::Break(Some(Value::IntValue(42)))));
+        // A `return` in a loop expression leaves the enclosing block with its value
+        assert_statement_eval("{ [loop { return 1; }]; return 2; }", Ok(StatementEval::Return(Value::IntValue(1))));
+        assert_statement_eval("{ i = 0; x = loop { if (i == 3) { return i; } i++; }; return 0; }", Ok(StatementEval::Return(Value::IntValue(3))));
+        assert_statement_eval("{ x = loop { y = loop { return 1; }; }; return 2; }", Ok(StatementEval::Return(Value::IntValue(1))));
+        assert_statement_eval("{ if (loop { return 1; }) { return 2; } }", Ok(StatementEval::Return(Value::IntValue(1))));
+    }
+
+    #[test]
+    fn test_match_eval() {
+        let text = "\
+fn name(n) {
+    match n + 1 {
+        1 => { return \"one\"; }
+        2 => { return \"two\"; }
+        -1 => { return \"minus one\"; }
+        _ => { return \"many\"; }
+    }
+}
+
+fn first(n) {
+    result = 0;
+    match n {
+        1 => { result = 1; }
+        _ => { return \"default\"; }
+        1 => { return \"never\"; }
+    }
+    return result;
+}
+
+fn main() {
+    match 3 {
+        1 => { return 1; }
+    }
+    return [name(0), name(1), name(0 - 2), name(5), first(1), first(2)];
+}";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let string = |s: &str| Value::StringValue(s.to_string());
+        assert_eq!(
+            module.run(),
+            Ok(StatementEval::Return(Value::List(Rc::new(vec![string("one"), string("two"), string("minus one"), string("many"), Value::IntValue(1), string("default")]))))
+        );
+    }
+
     #[test]
     fn test_error_when_using_variable_out_of_compound_scope() {
         // we want to test that a function does not have access to variables outside of its scope
@@ -159,54 +418,181 @@ fn main() {
         ".to_string();
         let tokens = tokenize(&file).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         println!("{module:?}");
         let result = module.run();
         println!("result = {result:?}");
         assert!(result.is_err());
     }
     
+    #[test]
+    fn test_if_body_scope() {
+        // The branches of an `if` are blocks: the variables they create are dropped afterward
+        assert_statement_eval("{ if (true) { b = 2; } return b; }", Err(EvalError::UnknownVariable("b".to_string())));
+        assert_statement_eval("{ if (false) {} else { b = 2; } return b; }", Err(EvalError::UnknownVariable("b".to_string())));
+        assert_statement_eval("{ if (false) {} else if (true) { b = 2; } return b; }", Err(EvalError::UnknownVariable("b".to_string())));
+        // while the enclosing variables are updated
+        assert_statement_eval("{ b = 1; if (true) { b = 2; } return b; }", Ok(StatementEval::Return(Value::IntValue(2))));
+        assert_statement_eval("{ b = 1; if (true) { c = b + 1; b = c; } return b; }", Ok(StatementEval::Return(Value::IntValue(2))));
+    }
+
+    #[test]
+    fn test_append_to_large_list() {
+        let list = |values: &[i64]| Value::List(Rc::new(values.iter().map(|v| Value::IntValue(*v)).collect()));
+        assert_statement_eval("{ a = []; i = 0; repeat 10000 { a = a + [i]; i++; } return [len(a), a[9999]]; }", Ok(StatementEval::Return(list(&[10000, 9999]))));
+
+        // The elements are appended to the list of the variable, which is not copied
+        let mut env = Environment::new();
+        let elements = |env: &Environment, name: &str| match env.get(name.into()) {
+            Some(Value::List(values)) => values.clone(),
+            value => panic!("Expected a list, got {value:?}")
+        };
+        let eval = |env: &mut Environment, text: &str| {
+            for statement in parse_statements(&tokenize(text).unwrap()).unwrap() {
+                statement.eval(env, None).unwrap();
+            }
+        };
+        eval(&mut env, "a = [1, 2];");
+        let before = Rc::as_ptr(&elements(&env, "a"));
+        eval(&mut env, "a = a + [3];");
+        assert_eq!(Rc::as_ptr(&elements(&env, "a")), before);
+        // A copy of the variable shares its elements, until one of them is modified
+        eval(&mut env, "b = a;");
+        assert!(Rc::ptr_eq(&elements(&env, "a"), &elements(&env, "b")));
+        eval(&mut env, "a = a + [4];");
+        assert!(!Rc::ptr_eq(&elements(&env, "a"), &elements(&env, "b")));
+        assert_eq!(Rc::as_ptr(&elements(&env, "b")), before);
+
+        // The other copies of the list are not modified
+        let nested = |lists: &[&[i64]]| Value::List(Rc::new(lists.iter().map(|values| list(values)).collect()));
+        assert_statement_eval("{ a = [1]; b = a; a = a + [2]; return [a, b]; }", Ok(StatementEval::Return(nested(&[&[1, 2], &[1]]))));
+        assert_statement_eval("{ a = [1]; b = [a, a]; a = a + [2]; return b; }", Ok(StatementEval::Return(nested(&[&[1], &[1]]))));
+        assert_statement_eval("{ a = [1]; f = fn() { return a; }; a = a + a; return [a, f()]; }", Ok(StatementEval::Return(nested(&[&[1, 1], &[1]]))));
+        // The other operations on lists fail
+        assert_statement_eval("{ a = [1]; return a + 2; }", Err(EvalError::Error("Binary operation not supported")));
+        assert_statement_eval("{ a = [1, 2]; return a - [2]; }", Err(EvalError::Error("Only addition is supported for list")));
+    }
+
+    #[test]
+    fn test_many_nested_blocks_with_large_environment() {
+        // Entering a block does not copy the environment, so this runs quickly
+        let variables: String = (0..500).map(|i| format!("a{i} = {i};")).collect();
+        let depth = 50;
+        let text = format!(
+            "{{ {variables} i = 0; repeat 2000 {{ {} x = a499 + i; {} i = i + 1; }} return i + a499; }}",
+            "{".repeat(depth),
+            "}".repeat(depth),
+        );
+        assert_statement_eval(&text, Ok(StatementEval::Return(Value::IntValue(2499))));
+    }
+
+    #[test]
+    fn test_local_function() {
+        let file = "\
+fn main() {
+    factor = 3;
+    fn triple(x) {
+        return factor * x;
+    }
+    fn fact(n) {
+        if (n == 0) {
+            return 1;
+        }
+        return n * fact(n - 1);
+    }
+    return triple(2) + fact(4);
+}
+
+fn other() {
+    return triple(1);
+}
+        ";
+        let tokens = tokenize(file).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(Value::IntValue(30))));
+        // The local function is not visible outside of its parent
+        assert_eq!(module.call_function("other".into(), vec![]), Err(EvalError::Error("Function not found")));
+    }
+
+    #[test]
+    fn test_assert_eq() {
+        assert_statement_eval("assert_eq 3, 1 + 2;", Ok(StatementEval::None));
+        assert_statement_eval("{ a = [1]; assert_eq [1], a; }", Ok(StatementEval::None));
+        assert_statement_eval("assert_eq 3, 5;", Err(EvalError::AssertionFailed("expected 3, got 5".to_string())));
+        assert_statement_eval("assert_eq \"a\", 1 == 1;", Err(EvalError::AssertionFailed("expected a, got true".to_string())));
+    }
+
     #[test]
     fn test_if_evaluation() {
         let text = "if (1) {return 3;}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        assert_eq!(statement.eval(&mut HashMap::new(), None), Ok(StatementEval::Return(Value::IntValue(3))))
+        assert_eq!(statement.eval(&mut Environment::new(), None), Ok(StatementEval::Return(Value::IntValue(3))))
     }
     
     #[test]
     fn test_else_evaluation() {
         let text = "if (0) {return 3;} else {return 4}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        assert_eq!(statement.eval(&mut HashMap::new(), None), Ok(StatementEval::Return(Value::IntValue(4))))
+        assert_eq!(statement.eval(&mut Environment::new(), None), Ok(StatementEval::Return(Value::IntValue(4))))
     }
     
+    #[test]
+    fn test_else_if_evaluation() {
+        let text = "if (a == 1) { return 1; } else if (a == 2) { return 2; } else if (a == 3) { return 3; } else { return 0; }";
+        let tokens = tokenize(text).unwrap();
+        let statement = &parse_statements(&tokens).unwrap()[0];
+        for (a, expected) in [(1, 1), (2, 2), (3, 3), (4, 0)] {
+            let mut env: Environment = [(Symbol::from("a"), Value::IntValue(a))].into_iter().collect();
+            assert_eq!(statement.eval(&mut env, None), Ok(StatementEval::Return(Value::IntValue(expected))));
+        }
+    }
+
+    #[test]
+    fn test_if_condition_conversion() {
+        assert_statement_eval("if (0) { return 1; } else { return 2; }", Ok(StatementEval::Return(Value::IntValue(2))));
+        assert_statement_eval("if (5) { return 1; } else { return 2; }", Ok(StatementEval::Return(Value::IntValue(1))));
+        assert_statement_eval("if (0 - 1) { return 1; }", Ok(StatementEval::Return(Value::IntValue(1))));
+        assert_statement_eval("if ([]) { return 1; }", Err(EvalError::Error("List can't be casted to bool")));
+        assert_statement_eval("if (\"\") { return 1; }", Err(EvalError::Error("String can't be casted to bool")));
+        assert_statement_eval("if (1 && 2) { return 1; }", Ok(StatementEval::Return(Value::IntValue(1))));
+    }
+
     #[test]
     fn test_if_evaluation_with_undefined_var() {
         let text = "if (n) {return 3;}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
-        assert!(matches!(result, Err(_)))
+        assert!(result.is_err())
     }
     
     #[test]
     fn test_return_statement_with_addition() {
         let text = "{return 1 + 1}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
         println!("Getting ready");
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(2))));
     }
     
+    #[test]
+    fn test_return_list_literal() {
+        let list = |values: &[i64]| Value::List(Rc::new(values.iter().map(|v| Value::IntValue(*v)).collect()));
+        assert_statement_eval("return [1, 2, 3];", Ok(StatementEval::Return(list(&[1, 2, 3]))));
+        assert_statement_eval("return [];", Ok(StatementEval::Return(list(&[]))));
+        assert_statement_eval("{ return [1, 2] + [3] }", Ok(StatementEval::Return(list(&[1, 2, 3]))));
+    }
+
     #[test]
     fn test_return_inside_compound() {
         let text = "
@@ -216,10 +602,10 @@ fn main() {
     return i;
 }
         ";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(1))));
     }
@@ -236,14 +622,85 @@ fn main() {
     return i;
 }
         ";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(10))));
     }
     
+    #[test]
+    fn test_return_from_loop() {
+        let text = "\
+fn first_square_above(n) {
+    i = 0;
+    loop {
+        if (i * i > n) {
+            return i;
+        }
+        i = i + 1;
+    }
+}
+
+fn first_even(list) {
+    i = 0;
+    while (i < len(list)) {
+        if (list[i] == 2 * (list[i] / 2)) {
+            return list[i];
+        }
+        i = i + 1;
+    }
+    return none;
+}";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.call_function("first_square_above".into(), vec![Value::IntValue(10)]), Ok(Value::IntValue(4)));
+        let list = Value::List(Rc::new(vec![Value::IntValue(3), Value::IntValue(5), Value::IntValue(8), Value::IntValue(9)]));
+        assert_eq!(module.call_function("first_even".into(), vec![list]), Ok(Value::IntValue(8)));
+    }
+
+    #[test]
+    fn test_loop_updates_outer_counter() {
+        let text = "{ count = 0; i = 0; loop { i = i + 1; if (i > 10) { break; } if (i > 0) { count = count + 1; } } return count; }";
+        assert_statement_eval(text, Ok(StatementEval::Return(Value::IntValue(10))));
+
+        // The variables created in the body are local to each iteration
+        assert_statement_eval("{ repeat 2 { local = 1; } return local; }", Err(EvalError::UnknownVariable("local".to_string())));
+        assert_statement_eval("{ i = 0; while (i < 3) { if (i == 1) { seen = 1; } i = i + 1; } return seen; }", Err(EvalError::UnknownVariable("seen".to_string())));
+    }
+
+    #[test]
+    fn test_loop_error() {
+        assert_statement_eval(
+            "{ i = 0; loop { i = i + 1; if (i == 3) { undefined; } } }",
+            Err(EvalError::UnknownVariable("undefined".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_repeat_eval() {
+        let text = "{ c = 0; repeat 5 { c = c + 1; } return c; }";
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
+        let result = ast[0].eval(&mut Environment::new(), None);
+        assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(5))));
+
+        // A negative or zero count runs the body zero times
+        let text = "{ c = 0; repeat 0 - 2 { c = c + 1; } repeat 0 { c = c + 1; } return c; }";
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
+        let result = ast[0].eval(&mut Environment::new(), None);
+        assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(0))));
+
+        // break and return are propagated out of the body
+        let text = "{ c = 0; repeat 5 { c = c + 1; if (c == 2) { break; } } repeat 5 { return c; } return 0; }";
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
+        let result = ast[0].eval(&mut Environment::new(), None);
+        assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(2))));
+    }
+
     #[test]
     fn test_len_function() {
         let text = "
@@ -252,10 +709,10 @@ fn main() {
     return len(a);
 }
         ";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(3))));
     }
@@ -269,10 +726,10 @@ fn main() {
     return len(a);
 }
         ";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         let statement = &ast[0];
-        let result = statement.eval(&mut HashMap::new(), None);
+        let result = statement.eval(&mut Environment::new(), None);
         println!("{result:?}");
         assert_eq!(result, Ok(StatementEval::Return(Value::IntValue(4))));
     }
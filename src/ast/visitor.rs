@@ -0,0 +1,165 @@
+use crate::ast::declaration::Declaration;
+use crate::ast::expression::Expr;
+use crate::ast::statement::Statement;
+
+/// A visitor over the AST, used to write static analysis passes.
+///
+/// Every method has a default implementation that walks into the children of the node,
+/// so a pass only has to override the nodes it is interested in. An overriding method
+/// must call the matching `walk_*` function to keep visiting the children.
+pub trait Visitor {
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        walk_declaration(self, declaration)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+}
+
+pub fn walk_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &Declaration) {
+    match declaration {
+        Declaration::Function(_, _, body) | Declaration::ConstFunction(_, _, body) => visitor.visit_statement(body),
+        Declaration::Const(_, value) => visitor.visit_expr(value),
+        Declaration::Struct(..) => {}
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::SimpleStatement(expr) | Statement::Return(expr) => visitor.visit_expr(expr),
+        Statement::CompoundStatement(statements) => {
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        }
+        Statement::If(condition, body, else_statement) => {
+            visitor.visit_expr(condition);
+            visitor.visit_statement(body);
+            if let Some(else_statement) = else_statement {
+                visitor.visit_statement(else_statement);
+            }
+        }
+        Statement::Loop(body) => visitor.visit_statement(body),
+        Statement::Repeat(count, body) | Statement::While(count, body) => {
+            visitor.visit_expr(count);
+            visitor.visit_statement(body);
+        }
+        Statement::DoWhile(body, condition) => {
+            visitor.visit_statement(body);
+            visitor.visit_expr(condition);
+        }
+        Statement::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Statement::LocalFn(_, _, body) => visitor.visit_statement(body),
+        Statement::AssertEq(expected, actual) => {
+            visitor.visit_expr(expected);
+            visitor.visit_expr(actual);
+        }
+        Statement::Match(subject, arms) => {
+            visitor.visit_expr(subject);
+            for (_, body) in arms {
+                visitor.visit_statement(body);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::ConstExpr(_) | Expr::IdentExpr(_) => {}
+        Expr::NegExpr(expr) | Expr::ParenthesisExpr(expr) | Expr::AssignmentExpr(_, expr) | Expr::ListAccess(_, expr) => {
+            visitor.visit_expr(expr)
+        }
+        Expr::BinaryExpr(l, _, r) | Expr::CompareExpr(l, _, r) | Expr::LogicalExpr(l, _, r) => {
+            visitor.visit_expr(l);
+            visitor.visit_expr(r);
+        }
+        Expr::ChainedCompare(first, comparisons) => {
+            visitor.visit_expr(first);
+            for (_, operand) in comparisons {
+                visitor.visit_expr(operand);
+            }
+        }
+        Expr::FunctionCall(_, args, keywords) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+            for (_, arg) in keywords {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::List(exprs) => {
+            for expr in exprs {
+                visitor.visit_expr(expr);
+            }
+        }
+        Expr::ListSlice(list, start, end) => {
+            visitor.visit_expr(list);
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expr(bound);
+            }
+        }
+        Expr::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Closure(_, body) | Expr::Loop(body) => visitor.visit_statement(body),
+        Expr::Struct(_, fields) => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::FieldAccess(value, _) => visitor.visit_expr(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::expression::Expr;
+    use crate::ast::visitor::{walk_expr, Visitor};
+    use crate::parser::Parser;
+    use crate::token::tokenize;
+
+    /// Counts the number of binary expressions
+    struct BinaryExprCounter(usize);
+
+    impl Visitor for BinaryExprCounter {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::BinaryExpr(_, _, _) = expr {
+                self.0 += 1;
+            }
+            walk_expr(self, expr)
+        }
+    }
+
+    #[test]
+    fn test_count_binary_expressions() {
+        let text = "\
+fn foo(a, b) {
+    c = a + b * 2;
+    if (c > 1 - a) {
+        return [c + 1, bar(a - b)];
+    }
+    loop { c = c + 1; break; }
+    return c;
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        let foo = module.get_function("foo".into()).unwrap();
+
+        let mut counter = BinaryExprCounter(0);
+        counter.visit_declaration(foo);
+        assert_eq!(counter.0, 6);
+    }
+}
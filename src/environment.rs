@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use crate::ast::expression::Value;
+use crate::symbol::Symbol;
+
+/// The variables visible during an evaluation.
+///
+/// Each block `{}` opens a new frame: a variable is looked up from the innermost frame outwards,
+/// and an assignment updates the innermost frame defining the variable. A variable that is not
+/// defined yet is created in the innermost frame, so it is not visible anymore once the block is
+/// left, without copying the enclosing variables.
+#[derive(Debug)]
+pub struct Environment {
+    frames: Vec<HashMap<Symbol, Value>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self { frames: vec![HashMap::new()] }
+    }
+
+    /// Returns the value of the variable in the innermost frame defining it
+    pub fn get(&self, name: Symbol) -> Option<&Value> {
+        self.frames.iter().rev().find_map(|frame| frame.get(&name))
+    }
+
+    /// Assigns the variable in the innermost frame that defines it, or creates it in the innermost frame
+    pub fn assign(&mut self, name: impl Into<Symbol>, value: Value) {
+        let name = name.into();
+        match self.frames.iter_mut().rev().find_map(|frame| frame.get_mut(&name)) {
+            Some(variable) => *variable = value,
+            None => self.insert(name, value)
+        }
+    }
+
+    /// Defines the variable in the innermost frame, shadowing the variables of the enclosing frames
+    pub fn insert(&mut self, name: impl Into<Symbol>, value: Value) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.insert(name.into(), value);
+        }
+    }
+
+    pub fn push_frame(&mut self) {
+        self.frames.push(HashMap::new());
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Returns all the visible variables, with their current value
+    pub fn variables(&self) -> HashMap<Symbol, Value> {
+        let mut variables = HashMap::new();
+        for frame in &self.frames {
+            variables.extend(frame.iter().map(|(name, value)| (*name, value.clone())));
+        }
+        variables
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<(Symbol, Value)> for Environment {
+    fn from_iter<T: IntoIterator<Item=(Symbol, Value)>>(iter: T) -> Self {
+        Self { frames: vec![iter.into_iter().collect()] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::expression::Value::IntValue;
+    use crate::environment::Environment;
+
+    #[test]
+    fn test_inner_frames_shadow_outer_frames() {
+        let mut env = Environment::new();
+        env.insert("a".to_string(), IntValue(1));
+        env.push_frame();
+        assert_eq!(env.get("a".into()), Some(&IntValue(1)));
+        env.insert("a".to_string(), IntValue(2));
+        env.insert("b".to_string(), IntValue(3));
+        assert_eq!(env.get("a".into()), Some(&IntValue(2)));
+        assert_eq!(env.variables().len(), 2);
+        env.pop_frame();
+        assert_eq!(env.get("a".into()), Some(&IntValue(1)));
+        assert_eq!(env.get("b".into()), None);
+    }
+
+    #[test]
+    fn test_assign_updates_enclosing_frames() {
+        let mut env = Environment::new();
+        env.insert("a".to_string(), IntValue(1));
+        env.push_frame();
+        env.assign("a".to_string(), IntValue(2));
+        env.assign("b".to_string(), IntValue(3));
+        assert_eq!(env.get("b".into()), Some(&IntValue(3)));
+        env.pop_frame();
+        assert_eq!(env.get("a".into()), Some(&IntValue(2)));
+        assert_eq!(env.get("b".into()), None);
+    }
+}
@@ -1,23 +1,99 @@
+use crate::ast::expression::Value;
+use crate::token::Token;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum EvalError {
     Error(&'static str),
     UnknownVariable(String),
-    MultipleError(Vec<Box<EvalError>>),
+    MultipleError(Vec<EvalError>),
+    /// An integer operation overflowed
+    IntegerOverflow,
+    /// A file could not be read or written
+    IoError(String),
+    /// An `assert_eq` statement failed, with a description of the values
+    AssertionFailed(String),
+    /// Too many nested function calls, see `Module::set_recursion_limit`
+    RecursionLimitExceeded,
+    /// Too many blocks evaluated, see `Module::set_step_limit`
+    StepLimitExceeded,
+    /// A keyword argument that does not match a parameter of the called function, or that
+    /// matches a parameter which is already bound
+    UnknownKeywordArgument(String),
+    /// An operation used values of the wrong types, such as comparing an `int` and a `string`
+    TypeError(String),
+    /// A field that is not declared by the struct, or that is given twice when constructing it
+    UnknownField(String),
+    /// An error in a nested function call, with the names of the functions of the module that
+    /// were being called, from the outermost to the one in which the error happened
+    WithBacktrace { inner: Box<EvalError>, stack: Vec<String> },
+    /// The evaluation was stopped by the program, with the message given to `error`
+    UserError(String),
+    /// A `return` in the body of a loop expression, which leaves the expression with its value.
+    /// The enclosing block turns it back into `StatementEval::Return`, and the parser rejects the
+    /// expressions that are not in a block, see `ParserError::ReturnOutsideFunction`.
+    Return(Value),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum TokenError {
-    UnknownChar(char)
+    UnknownChar(char),
+    /// A digit that does not belong to the base of the integer literal
+    InvalidDigit(char),
+    /// A base prefix (`0x`, `0b`, `0o`) that is not followed by any digit
+    MissingDigits,
+    /// An integer literal that does not fit in an `i64`
+    IntegerLiteralOverflow,
+    /// A character literal that is not a single character, or an escape, between quotes
+    InvalidCharLiteral,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ParserError {
     /// The parser did not find any match
     UnknownSyntax,
-    /// When a token is remaining after parsing is finished.
-    TokensNotParsed, 
+    /// When tokens are remaining after parsing is finished.
+    TokensNotParsed(Vec<Token>),
     ExpectedDifferentToken(&'static str),
     WrongFunctionArgumentList,
     WrongFunctionBody,
+    /// A `const fn` that performs input/output
+    ImpureConstFunction,
+    /// A `const fn` that calls, or refers to, a function of the module that is not const
+    ConstFunctionCallsFunction(String),
+    /// A `break value;` in the body of a `while`, `repeat` or `do` loop, which can't give a value
+    BreakValueInLoopStatement,
+    /// A `return` in a loop expression that is not in a function, such as the value of a global constant
+    ReturnOutsideFunction,
+    /// A token of the stream that could not be read, see `Parser::from_stream`
+    InvalidToken(TokenError),
+    /// The token at the furthest position reached by the parser, which could not be parsed,
+    /// with the tokens that were expected instead. When any identifier or literal was expected,
+    /// it is given with an empty value: `Ident("")`, `Integer(0)`, `String("")` or `Char('\0')`.
+    UnexpectedToken { position: usize, found: Option<Token>, expected: Vec<Token> },
+}
+
+/// Any error that can happen when evaluating source code
+#[derive(Debug, PartialEq)]
+pub enum InterpreterError {
+    Token(TokenError),
+    Parser(ParserError),
+    Eval(EvalError),
+}
+
+impl From<TokenError> for InterpreterError {
+    fn from(error: TokenError) -> Self {
+        InterpreterError::Token(error)
+    }
+}
+
+impl From<ParserError> for InterpreterError {
+    fn from(error: ParserError) -> Self {
+        InterpreterError::Parser(error)
+    }
+}
+
+impl From<EvalError> for InterpreterError {
+    fn from(error: EvalError) -> Self {
+        InterpreterError::Eval(error)
+    }
 }
@@ -0,0 +1,26 @@
+use std::fs;
+use std::io;
+
+/// Access to the files used by the `read_file` and `write_file` functions of the standard library.
+///
+/// It is a trait so that the tests can replace the files of the OS.
+pub trait FileSystem {
+    fn read_to_string(&self, path: &str) -> io::Result<String>;
+
+    /// Writes the contents to the file, and returns the number of bytes that were written
+    fn write(&self, path: &str, contents: &str) -> io::Result<usize>;
+}
+
+/// The files of the operating system
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &str, contents: &str) -> io::Result<usize> {
+        fs::write(path, contents)?;
+        Ok(contents.len())
+    }
+}
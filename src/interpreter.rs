@@ -0,0 +1,203 @@
+use crate::ast::expression::Value;
+use crate::ast::statement::StatementEval;
+use crate::environment::Environment;
+use crate::error::{EvalError, InterpreterError, ParserError};
+use crate::filesystem::FileSystem;
+use crate::module::Module;
+use crate::parser::{parse_expression, Parser};
+use crate::token::tokenize;
+
+/// Entry point to evaluate source code, from a string to a value.
+///
+/// The interpreter keeps the variables assigned by the evaluated expressions, and the last
+/// module that was run, so that its functions can be called by the next expressions.
+pub struct Interpreter {
+    vars: Environment,
+    module: Option<Module>,
+    /// Whether the modules are run in verbose mode, see `Module::set_verbose`
+    verbose: bool,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self { vars: Environment::new(), module: None, verbose: false }
+    }
+
+    /// Prints the value of each expression statement of `main` when running a module,
+    /// see [`Module::set_verbose`]
+    pub fn set_verbose(&mut self, enabled: bool) {
+        self.verbose = enabled;
+    }
+
+    /// Evaluates a single expression, such as `a = 1` or `foo(a) + 1`
+    pub fn eval_str(&mut self, src: &str) -> Result<Value, InterpreterError> {
+        let tokens = tokenize(src)?;
+        let ast = parse_expression(&tokens)?;
+        Ok(ast.eval(&mut self.vars, self.module.as_ref())?)
+    }
+
+    /// Parses a module and evaluates its `main` function, which returns the value of the module
+    pub fn run_module(&mut self, src: &str) -> Result<Value, InterpreterError> {
+        self.run_module_with_args(src, vec![])
+    }
+
+    /// Reads a module from a file and evaluates its `main` function with the arguments,
+    /// see [`Module::run_with_args`]
+    pub fn run_file(&mut self, fs: &dyn FileSystem, path: &str, args: Vec<String>) -> Result<Value, InterpreterError> {
+        let src = fs.read_to_string(path).map_err(|e| EvalError::IoError(e.to_string()))?;
+        self.run_module_with_args(&src, args)
+    }
+
+    /// Parses a module and evaluates its `main` function with the arguments,
+    /// see [`Module::run_with_args`]
+    pub fn run_module_with_args(&mut self, src: &str, args: Vec<String>) -> Result<Value, InterpreterError> {
+        let mut parser = Parser::new_owned(tokenize(src)?);
+        let module = match parser.parse_module() {
+            // The furthest failure tells why the remaining tokens could not be parsed
+            Err(ParserError::TokensNotParsed(_)) => return Err(parser.furthest_failure().into()),
+            module => module?,
+        };
+        let module = self.module.insert(module);
+        module.set_verbose(self.verbose);
+        match module.run_with_args(args)? {
+            StatementEval::Return(value) => Ok(value),
+            _ => Ok(Value::None),
+        }
+    }
+
+    /// Writes the variables to the file, as a JSON object.
+    /// The variables holding functions can't be serialized: they are skipped, and their names are returned.
+    pub fn save_variables(&self, fs: &dyn FileSystem, path: &str) -> Result<Vec<String>, InterpreterError> {
+        let mut variables: Vec<(String, Value)> = self.vars.variables().into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+        variables.sort();
+        let (saved, skipped): (Vec<_>, Vec<_>) = variables.into_iter().partition(|(_, value)| value.is_serializable());
+        let saved = Value::Map(saved.into_iter().map(|(name, value)| (Value::StringValue(name), value)).collect());
+        fs.write(path, &saved.to_json()).map_err(|e| EvalError::IoError(e.to_string()))?;
+        Ok(skipped.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Assigns the variables read from the file, written by [`Interpreter::save_variables`].
+    /// Returns the number of variables that were read.
+    pub fn load_variables(&mut self, fs: &dyn FileSystem, path: &str) -> Result<usize, InterpreterError> {
+        let json = fs.read_to_string(path).map_err(|e| EvalError::IoError(e.to_string()))?;
+        match Value::from_json(&json)? {
+            Value::Map(entries) => {
+                let count = entries.len();
+                for (name, value) in entries {
+                    // The keys of a JSON object are always strings
+                    if let Value::StringValue(name) = name {
+                        self.vars.assign(name, value);
+                    }
+                }
+                Ok(count)
+            }
+            _ => Err(EvalError::Error("The variables must be saved as a JSON object").into())
+        }
+    }
+
+    /// Forgets the variables and the functions of the last module
+    pub fn clear(&mut self) {
+        self.vars = Environment::new();
+        self.module = None;
+    }
+
+    /// Variables assigned by the evaluated expressions
+    pub fn variables(&self) -> &Environment {
+        &self.vars
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::expression::Value::{IntValue, StringValue};
+    use crate::error::{EvalError, InterpreterError, ParserError, TokenError};
+    use crate::filesystem::OsFileSystem;
+    use crate::interpreter::Interpreter;
+    use crate::token::{Comp, Op, Token};
+
+    #[test]
+    fn test_eval_str_keeps_variables() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_str("a = 20"), Ok(crate::ast::expression::Value::None));
+        assert_eq!(interpreter.eval_str("a * 2 + 2"), Ok(IntValue(42)));
+        assert_eq!(interpreter.variables().get("a".into()), Some(&IntValue(20)));
+    }
+
+    #[test]
+    fn test_run_module() {
+        let mut interpreter = Interpreter::new();
+        let src = "fn greet(name) { return \"Hello \" + name; } fn main() { return greet(\"world\"); }";
+        assert_eq!(interpreter.run_module(src), Ok(StringValue("Hello world".to_string())));
+        // The functions of the module can be called afterward
+        assert_eq!(interpreter.eval_str("greet(\"you\")"), Ok(StringValue("Hello you".to_string())));
+    }
+
+    #[test]
+    fn test_run_file() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.run_file(&OsFileSystem, "TestData/simple_file.txt", vec![]), Ok(IntValue(8)));
+        assert!(matches!(
+            interpreter.run_file(&OsFileSystem, "TestData/missing.txt", vec![]),
+            Err(InterpreterError::Eval(EvalError::IoError(_)))
+        ));
+        let src = "fn main(args) { return args[0]; }";
+        assert_eq!(interpreter.run_module_with_args(src, vec!["first".to_string()]), Ok(StringValue("first".to_string())));
+    }
+
+    #[test]
+    fn test_save_and_load_variables() {
+        let path = std::env::temp_dir().join(format!("interpreter_vars_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("a = 42").unwrap();
+        interpreter.eval_str("b = [\"x\", {1: true}]").unwrap();
+        interpreter.eval_str("f = fn(x) { return x; }").unwrap();
+        assert_eq!(interpreter.save_variables(&OsFileSystem, path), Ok(vec!["f".to_string()]));
+
+        let mut restored = Interpreter::new();
+        assert_eq!(restored.load_variables(&OsFileSystem, path), Ok(2));
+        assert_eq!(restored.eval_str("a + 1"), Ok(IntValue(43)));
+        // The keys of the maps are read back as strings
+        assert_eq!(restored.eval_str("b == [\"x\", {\"1\": true}]"), Ok(crate::ast::expression::Value::BoolValue(true)));
+        assert!(restored.eval_str("f").is_err());
+        std::fs::remove_file(path).unwrap();
+
+        assert!(matches!(
+            restored.load_variables(&OsFileSystem, path),
+            Err(InterpreterError::Eval(EvalError::IoError(_)))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_str("1 $ 2"), Err(InterpreterError::Token(TokenError::UnknownChar('$'))));
+        // After an operand, an operator is expected
+        match interpreter.eval_str("1 + 2 )") {
+            Err(InterpreterError::Parser(ParserError::UnexpectedToken { position: 3, found: Some(Token::RPar), expected })) => {
+                assert!(expected.contains(&Token::TokenOp(Op::Times)) && expected.contains(&Token::TokenComp(Comp::Lower)));
+                assert!(!expected.contains(&Token::RPar));
+            }
+            result => panic!("Expected an unexpected `)`, got {result:?}")
+        }
+        // After an operator, an operand is expected
+        match interpreter.eval_str("1 +") {
+            Err(InterpreterError::Parser(ParserError::UnexpectedToken { position: 2, found: None, expected })) => {
+                assert!(expected.contains(&Token::Ident(String::new())) && expected.contains(&Token::Integer(0)) && expected.contains(&Token::LPar));
+            }
+            result => panic!("Expected an unexpected end, got {result:?}")
+        }
+        assert_eq!(interpreter.eval_str("b"), Err(InterpreterError::Eval(EvalError::UnknownVariable("b".to_string()))));
+        // The expressions are not in a function, so they can't return
+        assert_eq!(interpreter.eval_str("x = loop { return 1; }"), Err(InterpreterError::Parser(ParserError::ReturnOutsideFunction)));
+        assert_eq!(interpreter.eval_str("f = fn() { return loop { return 1; }; }"), Ok(crate::ast::expression::Value::None));
+        assert_eq!(interpreter.eval_str("f()"), Ok(IntValue(1)));
+        assert_eq!(interpreter.run_module("fn foo() {}"), Err(InterpreterError::Eval(EvalError::Error("Function main not found"))));
+    }
+}
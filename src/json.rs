@@ -0,0 +1,337 @@
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+use crate::ast::expression::Value;
+use crate::error::EvalError;
+use crate::error::EvalError::Error;
+
+/// Conversion of values from and to JSON
+///
+/// ints, bools, strings and lists map to their JSON equivalent, maps map to objects
+/// and `None` maps to `null`. Functions are not serializable and are written as `null`.
+impl Value {
+    /// Serializes the value to JSON.
+    /// Since JSON objects only have string keys, the keys of a map that are not strings
+    /// are converted to strings.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write_json(self, &mut json);
+        json
+    }
+
+    /// Returns whether the value can be serialized, which is the case unless it holds a function.
+    /// Characters and structs are serialized as strings and objects, so they are read back as such.
+    pub fn is_serializable(&self) -> bool {
+        match self {
+            Value::Function(_) | Value::Closure(..) => false,
+            Value::List(values) => values.iter().all(Value::is_serializable),
+            Value::Map(entries) => entries.iter().all(|(key, value)| key.is_serializable() && value.is_serializable()),
+            Value::Struct(_, fields) => fields.iter().all(|(_, value)| value.is_serializable()),
+            Value::IntValue(_) | Value::BoolValue(_) | Value::StringValue(_) | Value::CharValue(_) | Value::None => true,
+        }
+    }
+
+    /// Deserializes a value from JSON
+    pub fn from_json(text: &str) -> Result<Value, EvalError> {
+        let mut reader = JsonReader { chars: text.chars().peekable() };
+        let value = reader.read_value()?;
+        reader.skip_whitespaces();
+        match reader.chars.next() {
+            None => Ok(value),
+            Some(_) => Err(Error("Malformed JSON: unexpected characters after the value"))
+        }
+    }
+}
+
+fn write_json(value: &Value, json: &mut String) {
+    match value {
+        Value::IntValue(i) => json.push_str(&i.to_string()),
+        Value::BoolValue(b) => json.push_str(&b.to_string()),
+        Value::StringValue(s) => write_json_string(s, json),
+        Value::CharValue(c) => write_json_string(&c.to_string(), json),
+        Value::List(values) => {
+            json.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_json(value, json);
+            }
+            json.push(']');
+        }
+        Value::Map(entries) => {
+            json.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                match key {
+                    Value::StringValue(key) => write_json_string(key, json),
+                    key => write_json_string(&key.to_string(), json),
+                }
+                json.push(':');
+                write_json(value, json);
+            }
+            json.push('}');
+        }
+        // The fields of a struct are serialized as an object, without the name of the struct
+        Value::Struct(_, fields) => {
+            json.push('{');
+            for (i, (field, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_json_string(field, json);
+                json.push(':');
+                write_json(value, json);
+            }
+            json.push('}');
+        }
+        // Functions can't be serialized
+        Value::Function(_) | Value::Closure(..) | Value::None => json.push_str("null"),
+    }
+}
+
+fn write_json_string(s: &str, json: &mut String) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// A recursive-descent JSON reader
+struct JsonReader<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl JsonReader<'_> {
+    fn skip_whitespaces(&mut self) {
+        while let Some(' ' | '\n' | '\r' | '\t') = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    /// Consumes the expected keyword, such as `true` or `null`
+    fn expect_keyword(&mut self, keyword: &str, value: Value) -> Result<Value, EvalError> {
+        for expected in keyword.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(Error("Malformed JSON: unknown keyword"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn read_value(&mut self) -> Result<Value, EvalError> {
+        self.skip_whitespaces();
+        match self.chars.peek() {
+            Some('t') => self.expect_keyword("true", Value::BoolValue(true)),
+            Some('f') => self.expect_keyword("false", Value::BoolValue(false)),
+            Some('n') => self.expect_keyword("null", Value::None),
+            Some('"') => Ok(Value::StringValue(self.read_string()?)),
+            Some('[') => self.read_list(),
+            Some('{') => self.read_map(),
+            Some('-' | '0'..='9') => self.read_number(),
+            Some(_) => Err(Error("Malformed JSON: unexpected character")),
+            None => Err(Error("Malformed JSON: unexpected end of input")),
+        }
+    }
+
+    fn read_number(&mut self) -> Result<Value, EvalError> {
+        let mut number = String::new();
+        if let Some('-') = self.chars.peek() {
+            number.push('-');
+            self.chars.next();
+        }
+        while let Some(c @ '0'..='9') = self.chars.peek() {
+            number.push(*c);
+            self.chars.next();
+        }
+        if let Some('.' | 'e' | 'E') = self.chars.peek() {
+            return Err(Error("Malformed JSON: only integer numbers are supported"));
+        }
+        number.parse().map(Value::IntValue).map_err(|_| Error("Malformed JSON: invalid number"))
+    }
+
+    fn read_string(&mut self) -> Result<String, EvalError> {
+        // Opening quote
+        self.chars.next();
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.read_unicode_escape()?),
+                    _ => return Err(Error("Malformed JSON: invalid escape in string")),
+                },
+                Some(c) => s.push(c),
+                None => return Err(Error("Malformed JSON: unterminated string")),
+            }
+        }
+    }
+
+    /// Reads the `XXXX` of a `\uXXXX` escape, including the second half of a surrogate pair
+    fn read_unicode_escape(&mut self) -> Result<char, EvalError> {
+        let high = self.read_hex_code()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or(Error("Malformed JSON: invalid unicode escape"));
+        }
+        if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+            return Err(Error("Malformed JSON: invalid unicode escape"));
+        }
+        let low = self.read_hex_code()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(Error("Malformed JSON: invalid unicode escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).ok_or(Error("Malformed JSON: invalid unicode escape"))
+    }
+
+    fn read_hex_code(&mut self) -> Result<u32, EvalError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.chars.next().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = 16 * code + digit,
+                None => return Err(Error("Malformed JSON: invalid unicode escape")),
+            }
+        }
+        Ok(code)
+    }
+
+    fn read_list(&mut self) -> Result<Value, EvalError> {
+        // Opening bracket
+        self.chars.next();
+        let mut values = vec![];
+        self.skip_whitespaces();
+        if let Some(']') = self.chars.peek() {
+            self.chars.next();
+            return Ok(Value::List(Rc::new(values)));
+        }
+        loop {
+            values.push(self.read_value()?);
+            self.skip_whitespaces();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::List(Rc::new(values))),
+                _ => return Err(Error("Malformed JSON: expected `,` or `]` in array")),
+            }
+        }
+    }
+
+    fn read_map(&mut self) -> Result<Value, EvalError> {
+        // Opening brace
+        self.chars.next();
+        let mut entries = vec![];
+        self.skip_whitespaces();
+        if let Some('}') = self.chars.peek() {
+            self.chars.next();
+            return Ok(Value::Map(entries));
+        }
+        loop {
+            self.skip_whitespaces();
+            if self.chars.peek() != Some(&'"') {
+                return Err(Error("Malformed JSON: object keys must be strings"));
+            }
+            let key = Value::StringValue(self.read_string()?);
+            self.skip_whitespaces();
+            if self.chars.next() != Some(':') {
+                return Err(Error("Malformed JSON: expected `:` in object"));
+            }
+            let value = self.read_value()?;
+            // When a key is repeated, the last value is kept
+            match entries.iter_mut().find(|(k, _)| *k == key) {
+                Some(entry) => entry.1 = value,
+                None => entries.push((key, value)),
+            }
+            self.skip_whitespaces();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Map(entries)),
+                _ => return Err(Error("Malformed JSON: expected `,` or `}` in object")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::ast::expression::Value;
+    use crate::ast::expression::Value::{BoolValue, IntValue, List, Map, StringValue};
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(IntValue(-12).to_json(), "-12");
+        assert_eq!(BoolValue(true).to_json(), "true");
+        assert_eq!(Value::None.to_json(), "null");
+        assert_eq!(StringValue("a\"b\\c\nd".to_string()).to_json(), "\"a\\\"b\\\\c\\nd\"");
+        assert_eq!(List(Rc::new(vec![IntValue(1), List(Rc::new(vec![])), StringValue("a".to_string())])).to_json(), "[1,[],\"a\"]");
+        assert_eq!(
+            Map(vec![(StringValue("a".to_string()), IntValue(1)), (IntValue(2), Value::None)]).to_json(),
+            "{\"a\":1,\"2\":null}"
+        );
+    }
+
+    #[test]
+    fn test_to_json_nested_list() {
+        let nested = List(Rc::new(vec![IntValue(1), List(Rc::new(vec![BoolValue(false), List(Rc::new(vec![Value::None]))])), List(Rc::new(vec![IntValue(2)]))]));
+        assert_eq!(nested.to_json(), "[1,[false,[null]],[2]]");
+        assert_eq!(Value::from_json(&nested.to_json()), Ok(nested));
+
+        let quoted = StringValue("say \"hi\"".to_string());
+        assert_eq!(quoted.to_json(), "\"say \\\"hi\\\"\"");
+        assert_eq!(Value::from_json(&quoted.to_json()), Ok(quoted));
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert_eq!(Value::from_json(" 42 "), Ok(IntValue(42)));
+        assert_eq!(Value::from_json("-1"), Ok(IntValue(-1)));
+        assert_eq!(Value::from_json("false"), Ok(BoolValue(false)));
+        assert_eq!(Value::from_json("null"), Ok(Value::None));
+        assert_eq!(Value::from_json("\"a\\u00e9\\ud83d\\ude00\\t\""), Ok(StringValue("aé😀\t".to_string())));
+        assert_eq!(
+            Value::from_json("[1, [true], {\"k\": [] }]"),
+            Ok(List(Rc::new(vec![IntValue(1), List(Rc::new(vec![BoolValue(true)])), Map(vec![(StringValue("k".to_string()), List(Rc::new(vec![])))])])))
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let values = [
+            IntValue(i64::MIN),
+            BoolValue(true),
+            Value::None,
+            StringValue("tab\t, quote \" and é".to_string()),
+            List(Rc::new(vec![IntValue(0), List(Rc::new(vec![StringValue("\\".to_string())]))])),
+            Map(vec![(StringValue("a".to_string()), List(Rc::new(vec![Value::None]))), (StringValue("b".to_string()), Map(vec![]))]),
+        ];
+        for value in values {
+            assert_eq!(Value::from_json(&value.to_json()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_malformed_json() {
+        for text in ["", "[1, 2", "[1 2]", "{1: 2}", "{\"a\" 2}", "\"abc", "tru", "1 2", "1.5", "\"\\x\""] {
+            assert!(Value::from_json(text).is_err(), "{text} should not be parsed");
+        }
+    }
+}
@@ -0,0 +1,15 @@
+
+pub mod analysis;
+pub mod ast;
+pub mod parser;
+pub mod shell;
+pub mod token;
+pub mod environment;
+pub mod error;
+pub mod filesystem;
+pub mod interpreter;
+pub mod json;
+pub mod module;
+pub mod optimizer;
+pub mod std;
+pub mod symbol;
@@ -1,14 +1,53 @@
-use crate::shell::Shell;
+use std::env;
+use std::process::ExitCode;
 
-mod ast;
-mod parser;
-mod shell;
-mod token;
-mod error;
-mod module;
-mod std;
+use interpreter::shell::Shell;
 
-fn main() {
+/// Stack of the thread evaluating the programs. The default limit of 1000 nested calls of
+/// `Module` needs a few tens of megabytes in debug builds, more than the 8 MB of the main thread.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Without arguments, starts the shell.
+/// With `--eval <expr>`, evaluates the expression.
+/// With `<file> [args...]` or `--run <file> [args...]`, runs the `main` function of the file,
+/// which receives the remaining arguments.
+/// With `--verbose <file> [args...]`, also prints the value of each expression statement of `main`.
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(args))
+        .expect("Failed to start the evaluation thread")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn run(args: Vec<String>) -> ExitCode {
     let mut shell = Shell::new();
-    shell.run()
+    let output = match args.as_slice() {
+        [] => {
+            shell.run();
+            return ExitCode::SUCCESS;
+        }
+        [flag, expr] if flag == "--eval" => shell.eval_once(expr),
+        [flag, path, args @ ..] if flag == "--run" => shell.run_file(path, args.to_vec()),
+        [flag, path, args @ ..] if flag == "--verbose" => {
+            shell.set_verbose(true);
+            shell.run_file(path, args.to_vec())
+        }
+        [flag, ..] if flag == "--eval" || flag == "--run" || flag == "--verbose" => {
+            eprintln!("Usage: interpreter [--run | --verbose] <file> [args...] or interpreter --eval <expr>");
+            return ExitCode::FAILURE;
+        }
+        [path, args @ ..] => shell.run_file(path, args.to_vec()),
+    };
+    match output {
+        Ok(Some(value)) => println!("{value}"),
+        Ok(None) => {}
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
 }
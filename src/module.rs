@@ -1,38 +1,355 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
 
+use crate::analysis::{called_functions, discarded_comparisons, undefined_variables, unreachable_functions, unreachable_statements, unused_variables, AnalysisWarning};
 use crate::ast::declaration::Declaration;
+use crate::ast::expression::{Expr, Value};
 use crate::ast::statement::StatementEval;
+use crate::environment::Environment;
 use crate::error::EvalError;
+use crate::optimizer::optimize;
+use crate::symbol::Symbol;
+
+/// Default maximum number of nested function calls.
+/// Each call uses a few kilobytes of stack (a few tens in debug builds), so the limit is only
+/// reached on a thread with a large enough stack, such as the one of the binary: use
+/// `Module::set_recursion_limit` to evaluate on smaller stacks.
+const DEFAULT_RECURSION_LIMIT: usize = 1_000;
+
+/// Maximum number of nested calls when the optimizer evaluates the calls to const functions,
+/// which happens on the stack of the caller of `Module::optimize`
+const FOLDING_RECURSION_LIMIT: usize = 32;
+
+/// Maximum number of blocks evaluated by the optimizer for each call to a const function,
+/// so that a call that never ends is not folded
+const FOLDING_STEP_LIMIT: usize = 100_000;
+
+/// The function given to `Module::set_statement_hook`
+type HookFn = dyn Fn(&Expr, &Value);
+
+/// The hook of `Module::set_statement_hook`, which can't be printed
+struct ModuleHook(Box<HookFn>);
+
+impl Debug for ModuleHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ModuleHook")
+    }
+}
 
 #[derive(Debug)]
 pub struct Module {
     declarations: Vec<Declaration>,
+    /// Maximum number of nested function calls
+    recursion_limit: usize,
+    /// Current number of nested function calls
+    call_depth: Cell<usize>,
+    /// Maximum number of blocks evaluated, if any
+    step_limit: Option<usize>,
+    /// Number of blocks evaluated since the outermost function call started
+    steps: Cell<usize>,
+    /// Names of the functions of the module being called, to report where the errors happen
+    call_stack: RefCell<Vec<Symbol>>,
+    /// Number of function bodies evaluated, without the calls whose result is read from the cache
+    evaluated_calls: Cell<usize>,
+    /// Whether the results of the function calls are cached
+    memoize: bool,
+    /// Called by `run` with the value of each expression statement of `main`
+    statement_hook: Option<ModuleHook>,
+    /// Results of the function calls, by function name and arguments
+    cache: RefCell<BTreeMap<(Symbol, Vec<Value>), Value>>,
+    /// Values of the global constants, or the error of the first one that could not be evaluated
+    globals: Result<HashMap<Symbol, Value>, EvalError>,
 }
 
 impl Module {
     pub fn new(declarations: Vec<Declaration>) -> Self {
-        Self { declarations }
+        let globals = Self::eval_globals(&declarations);
+        Self {
+            declarations,
+            globals,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            call_depth: Cell::new(0),
+            step_limit: None,
+            steps: Cell::new(0),
+            call_stack: RefCell::new(vec![]),
+            evaluated_calls: Cell::new(0),
+            memoize: false,
+            statement_hook: None,
+            cache: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Evaluates the global constants once, in the order of their declaration.
+    /// A constant can use the constants declared before it, but not the functions of the module.
+    fn eval_globals(declarations: &[Declaration]) -> Result<HashMap<Symbol, Value>, EvalError> {
+        let mut globals = Environment::new();
+        for declaration in declarations {
+            if let Declaration::Const(name, value) = declaration {
+                let value = value.eval(&mut globals, None)?;
+                globals.insert(*name, value);
+            }
+        }
+        Ok(globals.variables())
+    }
+
+    /// Returns the environment in which the functions are evaluated: the global constants,
+    /// and an inner frame for the variables of the function
+    fn base_environment(&self) -> Result<Environment, EvalError> {
+        let globals = self.globals.as_ref().map_err(Clone::clone)?;
+        let mut environment: Environment = globals.iter()
+            .map(|(name, value)| (*name, value.clone()))
+            .collect();
+        environment.push_frame();
+        Ok(environment)
+    }
+
+    /// Enables the cache of the results of the function calls, which is disabled by default.
+    /// It must only be enabled when the functions are pure: a function called again with
+    /// the same arguments is not evaluated anymore, so its side effects are not repeated.
+    pub fn set_memoization(&mut self, enabled: bool) {
+        self.memoize = enabled;
+        self.cache.borrow_mut().clear();
+    }
+
+    /// Number of times the body of a function was evaluated since the module was created
+    pub fn number_of_evaluated_calls(&self) -> usize {
+        self.evaluated_calls.get()
+    }
+
+    /// Enables the printing of the value of each expression statement `expr;` in the body of `main`
+    /// when the module is run, which is disabled by default. The nested statements, and the other
+    /// functions, are evaluated silently.
+    pub fn set_verbose(&mut self, enabled: bool) {
+        self.statement_hook = None;
+        if enabled {
+            self.set_statement_hook(|expr, value| println!("{expr} -> {value}"));
+        }
+    }
+
+    /// Like `Module::set_verbose`, but calls the hook with each expression statement of `main`
+    /// and its value instead of printing them
+    pub fn set_statement_hook(&mut self, hook: impl Fn(&Expr, &Value) + 'static) {
+        self.statement_hook = Some(ModuleHook(Box::new(hook)));
+    }
+
+    /// Sets the maximum number of nested function calls, after which the evaluation
+    /// fails with `EvalError::RecursionLimitExceeded`
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit;
+    }
+
+    /// Sets the maximum number of blocks evaluated by a call to `run` or `call_function`, such as
+    /// the bodies of the functions and the iterations of the loops, after which the evaluation
+    /// fails with `EvalError::StepLimitExceeded`. There is no limit by default.
+    pub fn set_step_limit(&mut self, limit: Option<usize>) {
+        self.step_limit = limit;
+    }
+
+    /// Counts the evaluation of a block, see `Module::set_step_limit`
+    pub(crate) fn count_step(&self) -> Result<(), EvalError> {
+        let steps = self.steps.get() + 1;
+        if self.step_limit.is_some_and(|limit| steps > limit) {
+            return Err(EvalError::StepLimitExceeded);
+        }
+        self.steps.set(steps);
+        Ok(())
+    }
+
+    /// Evaluates a function call, counting it in the depth of nested calls
+    pub fn with_call_depth(&self, call: impl FnOnce() -> Result<Value, EvalError>) -> Result<Value, EvalError> {
+        let depth = self.call_depth.get();
+        if depth >= self.recursion_limit {
+            return Err(EvalError::RecursionLimitExceeded);
+        }
+        self.call_depth.set(depth + 1);
+        let result = call();
+        self.call_depth.set(depth);
+        result
     }
 
     pub fn number_of_functions(&self) -> usize {
-        self.declarations.iter().filter(|d| matches!(d, Declaration::Function(_, _, _))).count()
+        self.declarations.iter().filter(|d| !matches!(d, Declaration::Const(..) | Declaration::Struct(..))).count()
     }
 
     /// Returns a function by its name
-    pub fn get_function(&self, name: &String) -> Option<&Declaration> {
+    pub fn get_function(&self, name: Symbol) -> Option<&Declaration> {
         self.declarations.iter().find(|d| match d {
-            Declaration::Function(fname, _, _) => fname == name
+            Declaration::Function(fname, _, _) | Declaration::ConstFunction(fname, _, _) => *fname == name,
+            Declaration::Const(..) | Declaration::Struct(..) => false,
+        })
+    }
+
+    /// Returns the names of the functions, in the order of their declaration
+    pub fn function_names(&self) -> Vec<&str> {
+        self.declarations.iter()
+            .filter_map(|d| match d {
+                Declaration::Function(name, _, _) | Declaration::ConstFunction(name, _, _) => Some(name.as_str()),
+                Declaration::Const(..) | Declaration::Struct(..) => None,
+            })
+            .collect()
+    }
+
+    /// Whether the module declares a function with this name.
+    /// The name is not interned, so it can be any string.
+    pub fn has_function(&self, name: &str) -> bool {
+        Symbol::lookup(name).is_some_and(|name| self.get_function(name).is_some())
+    }
+
+    /// Returns the names of the fields of a struct, by the name of the struct
+    pub fn get_struct(&self, name: Symbol) -> Option<&[Symbol]> {
+        self.declarations.iter().find_map(|d| match d {
+            Declaration::Struct(sname, fields) if *sname == name => Some(fields.as_slice()),
+            _ => None
         })
     }
 
-    /// Evaluate the `main` function
+    /// Calls a function of the module with the provided arguments
+    pub fn call_function(&self, name: Symbol, args: Vec<Value>) -> Result<Value, EvalError> {
+        match self.get_function(name) {
+            Some(function @ (Declaration::Function(_, arg_names, _) | Declaration::ConstFunction(_, arg_names, _))) => {
+                if arg_names.len() != args.len() {
+                    return Err(EvalError::Error("Wrong number of arguments in function call"));
+                }
+                let key = if self.memoize {
+                    let key = (name, args.clone());
+                    if let Some(result) = self.cache.borrow().get(&key) {
+                        return Ok(result.clone());
+                    }
+                    Some(key)
+                } else {
+                    None
+                };
+                // We don't provide the function call with all the variables, but just with the
+                // global constants and the provided arguments
+                let mut function_inputs = self.base_environment()?;
+                for (arg, value) in arg_names.iter().zip(args) {
+                    function_inputs.insert(arg.0, value);
+                }
+                self.evaluated_calls.set(self.evaluated_calls.get() + 1);
+                let result = self.with_call_stack(name, || self.with_call_depth(|| match function.eval(&mut function_inputs, Some(self))? {
+                    StatementEval::Return(result) => Ok(result),
+                    _ => Ok(Value::None),
+                }))?;
+                if let Some(key) = key {
+                    self.cache.borrow_mut().insert(key, result.clone());
+                }
+                Ok(result)
+            }
+            Some(Declaration::Const(..) | Declaration::Struct(..)) | None => Err(EvalError::Error("Function not found"))
+        }
+    }
+
+    /// Evaluates a call to a function of the module, which is on top of the call stack meanwhile.
+    /// An error in a nested call is reported with the call stack, see `EvalError::WithBacktrace`.
+    fn with_call_stack<T>(&self, name: Symbol, call: impl FnOnce() -> Result<T, EvalError>) -> Result<T, EvalError> {
+        if self.call_stack.borrow().is_empty() {
+            self.steps.set(0);
+        }
+        self.call_stack.borrow_mut().push(name);
+        let result = call().map_err(|error| match error {
+            // The stack of a recursion that is too deep is not worth printing
+            EvalError::WithBacktrace { .. } | EvalError::RecursionLimitExceeded => error,
+            error if self.call_stack.borrow().len() > 1 => EvalError::WithBacktrace {
+                inner: Box::new(error),
+                stack: self.call_stack.borrow().iter().map(Symbol::to_string).collect(),
+            },
+            error => error,
+        });
+        self.call_stack.borrow_mut().pop();
+        result
+    }
+
+    /// Evaluate the `main` function, without arguments
     pub fn run(&self) -> Result<StatementEval, EvalError> {
-        match self.get_function(&"main".to_string()) {
-            None => Err(EvalError::Error("Function main not found")),
-            Some(main) => main.eval(&mut HashMap::new(), Some(&self))
+        self.run_with_args(vec![])
+    }
+
+    /// Evaluate the `main` function with the command-line arguments.
+    /// When `main` declares a parameter, it receives the arguments as a list of strings.
+    pub fn run_with_args(&self, args: Vec<String>) -> Result<StatementEval, EvalError> {
+        match self.get_function(Symbol::intern("main")) {
+            Some(main @ (Declaration::Function(_, params, _) | Declaration::ConstFunction(_, params, _))) => {
+                let mut inputs = self.base_environment()?;
+                match params.as_slice() {
+                    [] => {}
+                    [param] => inputs.insert(param.0, Value::List(Rc::new(args.into_iter().map(Value::StringValue).collect()))),
+                    _ => return Err(EvalError::Error("The function main can only have a single parameter")),
+                }
+                let hook = self.statement_hook.as_ref().map(|hook| hook.0.as_ref());
+                self.with_call_stack(Symbol::intern("main"), || main.eval_with_hook(&mut inputs, Some(self), hook))
+            }
+            _ => Err(EvalError::Error("Function main not found")),
+        }
+    }
+
+    /// Reports, for all the functions, the variables that are assigned but never read
+    pub fn unused_variables(&self) -> Vec<AnalysisWarning> {
+        self.declarations.iter().flat_map(unused_variables).collect()
+    }
+
+    /// Reports, for all the functions, the comparisons whose result is discarded
+    pub fn discarded_comparisons(&self) -> Vec<AnalysisWarning> {
+        self.declarations.iter().flat_map(discarded_comparisons).collect()
+    }
+
+    /// Reports, for all the functions, the variables that are read where they are not defined.
+    /// Such a read fails with `EvalError::UnknownVariable` when it is evaluated.
+    pub fn undefined_variables(&self) -> Vec<AnalysisWarning> {
+        let globals: BTreeSet<&str> = self.declarations.iter()
+            .filter_map(|declaration| match declaration {
+                Declaration::Struct(..) => None,
+                declaration => Some(declaration.name()),
+            })
+            .collect();
+        self.declarations.iter().flat_map(|declaration| undefined_variables(declaration, &globals)).collect()
+    }
+
+    /// Reports, for all the functions, the statements that can never be evaluated
+    pub fn unreachable_statements(&self) -> Vec<AnalysisWarning> {
+        self.declarations.iter().flat_map(unreachable_statements).collect()
+    }
+
+    /// Optimizes all the functions, see [`optimize`].
+    /// The calls to const functions that recurse too deeply, or run for too long, are not folded.
+    pub fn optimize(&mut self) {
+        let mut const_functions = Module::new(self.declarations.iter()
+            .filter(|declaration| matches!(declaration, Declaration::ConstFunction(..) | Declaration::Const(..) | Declaration::Struct(..)))
+            .cloned()
+            .collect());
+        const_functions.set_recursion_limit(FOLDING_RECURSION_LIMIT);
+        const_functions.set_step_limit(Some(FOLDING_STEP_LIMIT));
+        for declaration in &mut self.declarations {
+            optimize(declaration, &const_functions);
         }
     }
 
+    /// Reports the functions that can't be reached from `main`.
+    /// A module without `main` has no unused function, since any function may be called.
+    pub fn unused_functions(&self) -> Vec<AnalysisWarning> {
+        match self.get_function(Symbol::intern("main")) {
+            Some(_) => unreachable_functions(&self.call_graph(), "main"),
+            None => vec![],
+        }
+    }
+
+    /// Maps each function to the functions of the module it calls, directly or by passing them as values.
+    /// Calls to the standard library, to variables and to local functions are not included.
+    pub fn call_graph(&self) -> HashMap<String, Vec<String>> {
+        self.declarations.iter()
+            .filter_map(|declaration| match declaration {
+                Declaration::Function(name, _, _) | Declaration::ConstFunction(name, _, _) => {
+                    let called = called_functions(declaration).into_iter()
+                        .filter(|called| self.has_function(called))
+                        .collect();
+                    Some((name.to_string(), called))
+                }
+                Declaration::Const(..) | Declaration::Struct(..) => None,
+            })
+            .collect()
+    }
+
     pub fn debug(&self) {
         for d in &self.declarations {
             println!("------");
@@ -43,19 +360,298 @@ impl Module {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
+    use crate::ast::expression::Value;
     use crate::ast::expression::Value::{BoolValue, IntValue};
     use crate::ast::statement::StatementEval;
+    use crate::environment::Environment;
+    use crate::error::{EvalError, ParserError};
     use crate::parser::Parser;
     use crate::token::tokenize;
 
+    #[test]
+    fn test_function_as_argument() {
+        let text = "\
+fn double(x) {
+    return 2 * x;
+}
+
+fn apply(f, x) {
+    return f(x);
+}
+
+fn main() {
+    g = double;
+    return apply(double, 20) + g(1) + apply(len, [1, 2, 3]);
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(45))));
+    }
+
+    #[test]
+    fn test_global_constants() {
+        let text = "\
+const SIZE = 10;
+const DOUBLE_SIZE = 2 * SIZE;
+
+fn area() {
+    return SIZE * SIZE;
+}
+
+fn perimeter() {
+    return 2 * DOUBLE_SIZE;
+}
+
+fn main() {
+    return area() + perimeter();
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.number_of_functions(), 3);
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(140))));
+        assert!(module.unused_functions().is_empty());
+
+        // A constant that can't be evaluated makes every call fail
+        let tokens = tokenize("const A = B; fn main() { return 1; }").unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.run(), Err(EvalError::UnknownVariable("B".to_string())));
+
+        // A constant is not in a function, so it can't return
+        let tokens = tokenize("const X = loop { return 1; }; fn main() { return X; }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_module().err(), Some(ParserError::ReturnOutsideFunction));
+        let tokens = tokenize("const F = fn() { x = loop { return 1; }; }; fn main() { return F(); }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_module().unwrap().run(), Ok(StatementEval::Return(IntValue(1))));
+    }
+
+    #[test]
+    fn test_struct() {
+        let text = "\
+struct Point { x, y }
+
+fn origin() {
+    return Point { y: 0, x: 0 };
+}
+
+fn main() {
+    p = Point { x: 1, y: 2 };
+    return p.x + 10 * p.y + origin().x;
+}";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.number_of_functions(), 2);
+        assert_eq!(module.run(), Ok(StatementEval::Return(IntValue(21))));
+        assert_eq!(
+            module.call_function("origin".into(), vec![]),
+            Ok(Value::Struct("Point".into(), vec![("x".into(), IntValue(0)), ("y".into(), IntValue(0))]))
+        );
+        assert_eq!(module.call_function("origin".into(), vec![]).unwrap().to_string(), "Point { x: 0, y: 0 }");
+
+        let run = |main: &str| {
+            let tokens = tokenize(&format!("struct Point {{ x, y }} fn main() {{ {main} }}")).unwrap();
+            Parser::new(&tokens).parse_module().unwrap().run()
+        };
+        assert_eq!(run("return Point { x: 1, y: 2 }.z;"), Err(EvalError::UnknownField("z".to_string())));
+        assert_eq!(run("return Point { x: 1, z: 2 };"), Err(EvalError::UnknownField("z".to_string())));
+        assert_eq!(run("return Point { x: 1, x: 2 };"), Err(EvalError::UnknownField("x".to_string())));
+        assert_eq!(run("return Point { x: 1 };"), Err(EvalError::Error("Missing field in struct construction")));
+        assert_eq!(run("return Line { x: 1 };"), Err(EvalError::Error("Struct not found")));
+        assert!(matches!(run("a = 1; return a.x;"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_function_names() {
+        let text = "const N = 2; fn add(a, b) { return a + b; } struct P { x } const fn twice(a) { return N * a; }";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.function_names(), vec!["add", "twice"]);
+        assert!(module.has_function("add"));
+        assert!(!module.has_function("N"));
+        assert!(!module.has_function("P"));
+        assert!(!module.has_function("main"));
+        assert_eq!(module.call_function("add".into(), vec![IntValue(1), IntValue(2)]), Ok(IntValue(3)));
+        assert_eq!(module.call_function("twice".into(), vec![IntValue(4)]), Ok(IntValue(8)));
+    }
+
+    #[test]
+    fn test_run_with_args() {
+        let module = |text: &str| Parser::new(&tokenize(text).unwrap()).parse_module().unwrap();
+        let args = || vec!["a".to_string(), "b".to_string()];
+        assert_eq!(module("fn main(args) { return len(args); }").run_with_args(args()), Ok(StatementEval::Return(IntValue(2))));
+        assert_eq!(module("fn main(args) { return args[1]; }").run_with_args(args()), Ok(StatementEval::Return(Value::StringValue("b".to_string()))));
+        assert_eq!(module("fn main(args) { return len(args); }").run(), Ok(StatementEval::Return(IntValue(0))));
+        // The arguments are ignored when `main` has no parameter
+        assert_eq!(module("fn main() { return 1; }").run_with_args(args()), Ok(StatementEval::Return(IntValue(1))));
+        assert!(module("fn main(a, b) { return 1; }").run_with_args(args()).is_err());
+    }
+
+    #[test]
+    fn test_verbose_run() {
+        let text = "\
+fn double(n) { n * 100; return n * 2; }
+fn main() {
+    x = 1;
+    x + 1;
+    double(x);
+    if (x == 1) { x = x + 1; x * 10; }
+    loop { x = x + 1; break; }
+    return double(x) + 1;
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut module = Parser::new(&tokens).parse_module().unwrap();
+        let expected = module.run();
+        assert_eq!(expected, Ok(StatementEval::Return(IntValue(7))));
+        module.set_verbose(true);
+        assert_eq!(module.run(), expected);
+
+        // Only the top-level expression statements of `main` are reported
+        let lines = Rc::new(RefCell::new(vec![]));
+        let hook_lines = lines.clone();
+        module.set_statement_hook(move |expr, value| hook_lines.borrow_mut().push(format!("{expr} -> {value}")));
+        assert_eq!(module.run(), expected);
+        assert_eq!(*lines.borrow(), ["x = 1 -> ", "x + 1 -> 2", "double(x) -> 2"]);
+
+        // The errors are not changed either
+        let tokens = tokenize("fn main() { 1; missing; return 1; }").unwrap();
+        let mut module = Parser::new(&tokens).parse_module().unwrap();
+        module.set_verbose(true);
+        assert_eq!(module.run(), Err(EvalError::UnknownVariable("missing".to_string())));
+        let tokens = tokenize("fn main() { 1; break; return 1; }").unwrap();
+        let mut module = Parser::new(&tokens).parse_module().unwrap();
+        module.set_verbose(true);
+        assert_eq!(module.run(), Ok(StatementEval::None));
+    }
+
+    #[test]
+    fn test_user_error() {
+        let text = "\
+fn check(n) { if (n < 0) { error(\"boom\"); } return n; }
+fn main() { check(1); check(-1); return 0; }";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        assert_eq!(module.call_function("check".into(), vec![IntValue(1)]), Ok(IntValue(1)));
+        assert_eq!(module.call_function("check".into(), vec![IntValue(-1)]), Err(EvalError::UserError("boom".to_string())));
+        let inner = Box::new(EvalError::UserError("boom".to_string()));
+        assert_eq!(module.run(), Err(EvalError::WithBacktrace { inner, stack: vec!["main".to_string(), "check".to_string()] }));
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let text = "\
+fn bar(n) { return n + missing; }
+fn foo(n) { return bar(n) + 1; }
+fn main() { return foo(1) * 2; }";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let stack = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let inner = Box::new(EvalError::UnknownVariable("missing".to_string()));
+        assert_eq!(module.run(), Err(EvalError::WithBacktrace { inner: inner.clone(), stack: stack(&["main", "foo", "bar"]) }));
+        assert_eq!(module.call_function("foo".into(), vec![IntValue(1)]), Err(EvalError::WithBacktrace { inner, stack: stack(&["foo", "bar"]) }));
+        // Without nested calls, there is no backtrace
+        assert_eq!(module.call_function("bar".into(), vec![IntValue(1)]), Err(EvalError::UnknownVariable("missing".to_string())));
+        // The stack is emptied after an error
+        assert_eq!(module.call_function("bar".into(), vec![IntValue(1)]), Err(EvalError::UnknownVariable("missing".to_string())));
+    }
+
+    #[test]
+    fn test_call_function_errors() {
+        let text = "fn foo(a) { return a; } fn main() { b = 1; return b(2); }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.call_function("foo".into(), vec![IntValue(1)]), Ok(IntValue(1)));
+        assert!(module.call_function("foo".into(), vec![]).is_err());
+        assert_eq!(module.call_function("bar".into(), vec![]), Err(EvalError::Error("Function not found")));
+        // `b` holds an int, so `b(2)` looks up for a function named `b`
+        assert_eq!(module.run(), Err(EvalError::Error("Function not found")));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let text = "\
+fn recursive(n) {
+    return recursive(n + 1);
+}
+
+fn count(n) {
+    if (n == 0) {
+        return 0;
+    }
+    return 1 + count(n - 1);
+}
+
+fn main() {
+    return recursive(0);
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let mut module = parser.parse_module().unwrap();
+        // The stack of the test threads is too small for the default limit
+        module.set_recursion_limit(10);
+        assert_eq!(module.run(), Err(EvalError::RecursionLimitExceeded));
+        // The depth is reset after the error
+        assert_eq!(module.call_function("count".into(), vec![IntValue(9)]), Ok(IntValue(9)));
+        assert_eq!(module.call_function("count".into(), vec![IntValue(10)]), Err(EvalError::RecursionLimitExceeded));
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let text = "\
+fn spin() {
+    loop { }
+}
+
+fn count(n) {
+    i = 0;
+    while (i < n) { i = i + 1; }
+    return i;
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let mut module = parser.parse_module().unwrap();
+        module.set_step_limit(Some(100));
+        assert_eq!(module.call_function("spin".into(), vec![]), Err(EvalError::StepLimitExceeded));
+        // Each call has its own budget, in which the body of the function and 99 iterations fit
+        assert_eq!(module.call_function("count".into(), vec![IntValue(99)]), Ok(IntValue(99)));
+        assert_eq!(module.call_function("count".into(), vec![IntValue(100)]), Err(EvalError::StepLimitExceeded));
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let text = "\
+fn bar(x) {
+    print(x);
+    return x;
+}
+
+fn foo(f) {
+    return bar(1) + f(bar(2));
+}
+
+fn main() {
+    a = 1;
+    return foo(bar) + a;
+}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        let graph = module.call_graph();
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph["main"], vec!["bar".to_string(), "foo".to_string()]);
+        assert_eq!(graph["foo"], vec!["bar".to_string()]);
+        assert!(graph["bar"].is_empty());
+    }
+
     #[test]
     fn test_eval_main() {
         let text = crate::parser::tests::get_simple_file();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
         let result = module.run();
         println!("{result:?}");
     }
@@ -65,18 +661,18 @@ mod tests {
         let text = std::fs::read_to_string("TestData/if_else_loops.txt").unwrap();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
 
-        let bar = module.get_function(&"bar".to_string()).unwrap();
-        let result = bar.eval(&mut HashMap::new(), Some(&module));
+        let bar = module.get_function("bar".into()).unwrap();
+        let result = bar.eval(&mut Environment::new(), Some(&module));
         assert_eq!(result, Ok(StatementEval::Return(IntValue(0))));
 
-        let dog = module.get_function(&"dog".to_string()).unwrap();
-        let result = dog.eval(&mut HashMap::new(), Some(&module));
+        let dog = module.get_function("dog".into()).unwrap();
+        let result = dog.eval(&mut Environment::new(), Some(&module));
         assert_eq!(result, Ok(StatementEval::Return(IntValue(0))));
 
-        let cat = module.get_function(&"cat".to_string()).unwrap();
-        let result = cat.eval(&mut HashMap::new(), Some(&module));
+        let cat = module.get_function("cat".into()).unwrap();
+        let result = cat.eval(&mut Environment::new(), Some(&module));
         assert_eq!(result, Ok(StatementEval::Return(IntValue(20))));
     }
 
@@ -85,14 +681,14 @@ mod tests {
         let text = std::fs::read_to_string("TestData/if_else_loops.txt").unwrap();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let module = parser.parse_module();
+        let module = parser.parse_module().unwrap();
 
-        let returns_true = module.get_function(&"returns_true".to_string()).unwrap();
-        let result = returns_true.eval(&mut HashMap::new(), Some(&module));
+        let returns_true = module.get_function("returns_true".into()).unwrap();
+        let result = returns_true.eval(&mut Environment::new(), Some(&module));
         assert_eq!(result, Ok(StatementEval::Return(BoolValue(true))));
 
-        let returns_false = module.get_function(&"returns_false".to_string()).unwrap();
-        let result = returns_false.eval(&mut HashMap::new(), Some(&module));
+        let returns_false = module.get_function("returns_false".into()).unwrap();
+        let result = returns_false.eval(&mut Environment::new(), Some(&module));
         assert_eq!(result, Ok(StatementEval::Return(BoolValue(false))));
     }
     
@@ -102,10 +698,10 @@ mod tests {
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
         
-        let mut inputs = HashMap::new();
+        let mut inputs = Environment::new();
         
-        let module = parser.parse_module();
-        let func = module.get_function(&"fib".to_string()).unwrap();
+        let module = parser.parse_module().unwrap();
+        let func = module.get_function("fib".into()).unwrap();
 
         inputs.insert("n".to_string(), IntValue(0));
         assert_eq!(func.eval(&mut inputs, Some(&module)), Ok(StatementEval::Return(IntValue(0))));
@@ -135,19 +731,84 @@ mod tests {
         assert_eq!(func.eval(&mut inputs, Some(&module)), Ok(StatementEval::Return(IntValue(610))));
     }
     
+    #[test]
+    fn test_optimized_module() {
+        let text = std::fs::read_to_string("TestData/fibonacci.txt").unwrap();
+        let tokens = tokenize(&text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let mut module = parser.parse_module().unwrap();
+        module.optimize();
+        assert_eq!(module.call_function("fib".into(), vec![IntValue(10)]), Ok(IntValue(55)));
+    }
+
+    #[test]
+    fn test_loop_heavy_program() {
+        // The variables and the functions are looked up by their interned names
+        let text = "\
+fn step(x) { return x + 1; }
+fn main() {
+    total = 0;
+    i = 0;
+    while (i < 200) {
+        j = 0;
+        while (j < 100) {
+            total = total + step(j);
+            j++;
+        }
+        i++;
+    }
+    return [total, i];
+}";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let expected = Value::List(Rc::new(vec![IntValue(200 * 5050), IntValue(200)]));
+        assert_eq!(module.run(), Ok(StatementEval::Return(expected)));
+        assert_eq!(module.number_of_evaluated_calls(), 200 * 100);
+    }
+
+    #[test]
+    fn test_chained_comparison_evaluates_operands_once() {
+        let text = "fn f(x) { return x; } fn main() { return [1 < f(5) < 10, 1 < f(0) < f(10)]; }";
+        let tokens = tokenize(text).unwrap();
+        let module = Parser::new(&tokens).parse_module().unwrap();
+        let expected = Value::List(Rc::new(vec![Value::BoolValue(true), Value::BoolValue(false)]));
+        assert_eq!(module.run(), Ok(StatementEval::Return(expected)));
+        // The second chain stops at the first comparison that is false
+        assert_eq!(module.number_of_evaluated_calls(), 2);
+    }
+
+    #[test]
+    fn test_memoized_fibonacci() {
+        let text = std::fs::read_to_string("TestData/fibonacci.txt").unwrap();
+        let tokens = tokenize(&text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let mut module = parser.parse_module().unwrap();
+        // Without the cache, `fib(n)` makes `2 * fib(n + 1) - 1` calls
+        assert_eq!(module.call_function("fib".into(), vec![IntValue(15)]), Ok(IntValue(610)));
+        assert_eq!(module.number_of_evaluated_calls(), 1973);
+
+        module.set_memoization(true);
+        // With the cache, each value of `n` is evaluated once
+        assert_eq!(module.call_function("fib".into(), vec![IntValue(35)]), Ok(IntValue(9227465)));
+        assert_eq!(module.number_of_evaluated_calls(), 1973 + 36);
+        // the values that are already cached are not evaluated again
+        assert_eq!(module.call_function("fib".into(), vec![IntValue(80)]), Ok(IntValue(23416728348467685)));
+        assert_eq!(module.number_of_evaluated_calls(), 1973 + 81);
+    }
+
     #[test]
     fn test_printing_fibo() {
         let text = std::fs::read_to_string("TestData/fibonacci.txt").unwrap();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
 
-        let mut inputs = HashMap::new();
+        let mut inputs = Environment::new();
 
-        let module = parser.parse_module();
-        let func = module.get_function(&"print_fib_until".to_string()).unwrap();
+        let module = parser.parse_module().unwrap();
+        let func = module.get_function("print_fib_until".into()).unwrap();
         
         inputs.insert("n".to_string(), IntValue(10));
-        func.eval(&mut inputs, Some(&module));
+        assert!(func.eval(&mut inputs, Some(&module)).is_ok());
         
     }
 }
\ No newline at end of file
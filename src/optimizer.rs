@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::declaration::Declaration;
+use crate::ast::expression::Expr;
+use crate::ast::expression::Value::IntValue;
+use crate::ast::expression::Value;
+use crate::ast::statement::Statement;
+use crate::ast::visitor::{walk_expr, walk_statement, Visitor};
+use crate::module::Module;
+use crate::symbol::Symbol;
+use crate::token::Op;
+
+/// Optimizes the body of a function with constant folding and constant propagation.
+///
+/// The optimization is conservative: a variable is only replaced by its constant value when
+/// the assignment is certain to be the last one executed before the use. Variables assigned
+/// in a branch of a `if` or in the body of a loop are not propagated.
+///
+/// The calls to the functions of `const_functions` with constant arguments are replaced by their result.
+pub fn optimize(declaration: &mut Declaration, const_functions: &Module) {
+    match declaration {
+        Declaration::Function(_, args, body) | Declaration::ConstFunction(_, args, body) => {
+            // Variables holding a function shadow the functions of the module
+            let mut assigned = AssignedVariables::default();
+            assigned.visit_statement(body);
+            let shadowed: HashSet<Symbol> = args.iter().map(|arg| arg.0).chain(assigned.names).collect();
+            let mut pass = ConstantPropagation { constants: HashMap::new(), const_functions: Some((const_functions, &shadowed)) };
+            pass.optimize_statement(body)
+        }
+        Declaration::Const(_, value) => ConstantPropagation::default().optimize_expr(value),
+        Declaration::Struct(..) => {}
+    }
+}
+
+/// Constant values of the variables, at the current point of the function
+#[derive(Default)]
+struct ConstantPropagation<'a> {
+    constants: HashMap<Symbol, Value>,
+    /// The functions that can be evaluated, and the variables of the function that shadow them
+    const_functions: Option<(&'a Module, &'a HashSet<Symbol>)>,
+}
+
+impl ConstantPropagation<'_> {
+    /// Returns a copy of the current state, to optimize code that may not be executed
+    fn inner(&self) -> Self {
+        ConstantPropagation { constants: self.constants.clone(), const_functions: self.const_functions }
+    }
+
+    /// Evaluates a call to a const function, when all the arguments are constants
+    fn eval_const_call(&self, name: Symbol, args: &[Expr]) -> Option<Value> {
+        let (functions, shadowed) = self.const_functions?;
+        if shadowed.contains(&name) {
+            return None;
+        }
+        let args = args.iter()
+            .map(|arg| match arg {
+                // A function passed as argument may be impure
+                Expr::ConstExpr(Value::Function(_) | Value::Closure(..)) => None,
+                Expr::ConstExpr(value) => Some(value.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<Value>>>()?;
+        // Calls that fail are left to fail at runtime
+        functions.call_function(name, args).ok()
+    }
+
+    /// Forgets the variables that are assigned by the statement
+    fn forget_assigned(&mut self, statement: &Statement) {
+        let mut assigned = AssignedVariables::default();
+        assigned.visit_statement(statement);
+        for name in assigned.names {
+            self.constants.remove(&name);
+        }
+    }
+
+    /// Optimizes a statement that may not be executed, or that may be executed many times
+    fn optimize_conditional(&mut self, statement: &mut Statement) {
+        self.forget_assigned(statement);
+        let mut inner = self.inner();
+        inner.optimize_statement(statement);
+    }
+
+    fn optimize_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::SimpleStatement(expr) | Statement::Return(expr) => self.optimize_expr(expr),
+            Statement::CompoundStatement(statements) => {
+                let mut inner = self.inner();
+                for statement in statements.iter_mut() {
+                    inner.optimize_statement(statement);
+                }
+                // Depending on the scoping rules, the block may have modified the variables
+                self.forget_assigned(statement);
+            }
+            Statement::If(condition, body, else_statement) => {
+                self.optimize_expr(condition);
+                self.optimize_conditional(body);
+                if let Some(else_statement) = else_statement {
+                    self.optimize_conditional(else_statement);
+                }
+            }
+            Statement::Loop(body) => self.optimize_conditional(body),
+            Statement::Repeat(count, body) => {
+                self.optimize_expr(count);
+                self.optimize_conditional(body);
+            }
+            Statement::While(condition, body) => {
+                // The condition is evaluated again after the body
+                self.forget_assigned(body);
+                self.optimize_expr(condition);
+                self.optimize_conditional(body);
+            }
+            Statement::DoWhile(body, condition) => {
+                // The body is evaluated again after the condition
+                self.forget_assigned(body);
+                self.optimize_conditional(body);
+                self.optimize_expr(condition);
+            }
+            Statement::AssertEq(expected, actual) => {
+                self.optimize_expr(expected);
+                self.optimize_expr(actual);
+            }
+            Statement::Match(subject, arms) => {
+                self.optimize_expr(subject);
+                for (_, body) in arms.iter_mut() {
+                    self.optimize_conditional(body);
+                }
+            }
+            Statement::Break(value) => {
+                if let Some(value) = value {
+                    self.optimize_expr(value);
+                }
+            }
+            Statement::LocalFn(name, _, body) => {
+                self.constants.remove(name);
+                ConstantPropagation::default().optimize_statement(body);
+            }
+        }
+    }
+
+    fn optimize_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::ConstExpr(_) => {}
+            Expr::IdentExpr(name) => {
+                if let Some(value) = self.constants.get(name) {
+                    *expr = Expr::ConstExpr(value.clone());
+                }
+            }
+            Expr::AssignmentExpr(name, value) => {
+                self.optimize_expr(value);
+                match value.as_ref() {
+                    Expr::ConstExpr(constant) => self.constants.insert(*name, constant.clone()),
+                    _ => self.constants.remove(name),
+                };
+            }
+            Expr::NegExpr(inner) => {
+                self.optimize_expr(inner);
+                if let Expr::ConstExpr(IntValue(i)) = inner.as_ref() {
+                    if let Some(result) = i.checked_neg() {
+                        *expr = Expr::ConstExpr(IntValue(result));
+                    }
+                }
+            }
+            Expr::ParenthesisExpr(inner) => {
+                self.optimize_expr(inner);
+                if let Expr::ConstExpr(value) = inner.as_ref() {
+                    *expr = Expr::ConstExpr(value.clone());
+                }
+            }
+            Expr::BinaryExpr(l, op, r) => {
+                self.optimize_expr(l);
+                self.optimize_expr(r);
+                if let (Expr::ConstExpr(IntValue(l)), Expr::ConstExpr(IntValue(r))) = (l.as_ref(), r.as_ref()) {
+                    if let Some(result) = fold_binary(*l, op, *r) {
+                        *expr = Expr::ConstExpr(IntValue(result));
+                    }
+                }
+            }
+            Expr::CompareExpr(l, comp, r) => {
+                self.optimize_expr(l);
+                self.optimize_expr(r);
+                if let (Expr::ConstExpr(l), Expr::ConstExpr(r)) = (l.as_ref(), r.as_ref()) {
+                    // Comparisons that fail are left to fail at runtime
+                    if let Ok(result) = Expr::eval_compare_expr(l, comp, r) {
+                        *expr = Expr::ConstExpr(result);
+                    }
+                }
+            }
+            Expr::ChainedCompare(first, comparisons) => {
+                self.optimize_expr(first);
+                // The operands after the first one are not always evaluated
+                for (_, operand) in comparisons.iter() {
+                    let mut assigned = AssignedVariables::default();
+                    assigned.visit_expr(operand);
+                    for name in assigned.names {
+                        self.constants.remove(&name);
+                    }
+                }
+                for (_, operand) in comparisons.iter_mut() {
+                    self.inner().optimize_expr(operand);
+                }
+            }
+            Expr::LogicalExpr(l, _, r) => {
+                self.optimize_expr(l);
+                // The right side is not always evaluated
+                let mut assigned = AssignedVariables::default();
+                assigned.visit_expr(r);
+                for name in assigned.names {
+                    self.constants.remove(&name);
+                }
+                let mut inner = self.inner();
+                inner.optimize_expr(r);
+            }
+            Expr::FunctionCall(name, args, keywords) => {
+                for arg in args.iter_mut() {
+                    self.optimize_expr(arg);
+                }
+                for (_, arg) in keywords.iter_mut() {
+                    self.optimize_expr(arg);
+                }
+                if keywords.is_empty() {
+                    if let Some(value) = self.eval_const_call(*name, args) {
+                        *expr = Expr::ConstExpr(value);
+                    }
+                }
+            }
+            Expr::List(values) => {
+                for value in values {
+                    self.optimize_expr(value);
+                }
+            }
+            Expr::Map(entries) => {
+                for (key, value) in entries {
+                    self.optimize_expr(key);
+                    self.optimize_expr(value);
+                }
+            }
+            Expr::ListAccess(_, index) => self.optimize_expr(index),
+            Expr::ListSlice(list, start, end) => {
+                self.optimize_expr(list);
+                for bound in [start, end].into_iter().flatten() {
+                    self.optimize_expr(bound);
+                }
+            }
+            // The body of a closure is evaluated later, with the variables it captured
+            Expr::Closure(_, body) => ConstantPropagation::default().optimize_statement(body),
+            Expr::Loop(body) => self.optimize_conditional(body),
+            Expr::Struct(_, fields) => {
+                for (_, value) in fields {
+                    self.optimize_expr(value);
+                }
+            }
+            Expr::FieldAccess(value, _) => self.optimize_expr(value),
+        }
+    }
+}
+
+/// Evaluates an integer operation, unless it would fail at runtime
+fn fold_binary(l: i64, op: &Op, r: i64) -> Option<i64> {
+    match op {
+        Op::Plus => l.checked_add(r),
+        Op::Minus => l.checked_sub(r),
+        Op::Times => l.checked_mul(r),
+        Op::Div => l.checked_div(r),
+        Op::Pow => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+    }
+}
+
+/// Collects the names of all the variables that are assigned
+#[derive(Default)]
+struct AssignedVariables {
+    names: Vec<Symbol>,
+}
+
+impl Visitor for AssignedVariables {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            // A local function is stored as a variable
+            Statement::LocalFn(name, _, _) => self.names.push(*name),
+            _ => walk_statement(self, statement)
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::AssignmentExpr(name, _) => {
+                self.names.push(*name);
+                walk_expr(self, expr);
+            }
+            // Assignments in a closure only modify the variables of the closure
+            Expr::Closure(_, _) => {}
+            _ => walk_expr(self, expr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::declaration::Declaration;
+    use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, ConstExpr, FunctionCall, IdentExpr};
+    use crate::ast::expression::Value::{BoolValue, IntValue};
+    use crate::ast::statement::Statement;
+    use crate::ast::statement::Statement::{CompoundStatement, SimpleStatement};
+    use crate::error::ParserError;
+    use crate::module::Module;
+    use crate::optimizer::optimize;
+    use crate::parser::Parser;
+    use crate::token::tokenize;
+
+    /// Returns the statements of the optimized function
+    fn optimized_body(text: &str) -> Vec<Statement> {
+        let tokens = tokenize(text).unwrap();
+        let mut declaration = Parser::new(&tokens).parse_declaration().unwrap().unwrap();
+        optimize(&mut declaration, &Module::new(vec![]));
+        match declaration {
+            Declaration::Function(_, _, CompoundStatement(statements)) => statements,
+            _ => panic!("Expected a function with a body")
+        }
+    }
+
+    fn assignment(name: &str, expr: crate::ast::expression::Expr) -> Statement {
+        SimpleStatement(AssignmentExpr(name.into(), Box::new(expr)))
+    }
+
+    #[test]
+    fn test_constant_propagation() {
+        let body = optimized_body("fn foo() { a = 5; b = a + 3; return b * 2 == 16; }");
+        assert_eq!(body[1], assignment("b", ConstExpr(IntValue(8))));
+        assert_eq!(body[2], Statement::Return(ConstExpr(BoolValue(true))));
+    }
+
+    #[test]
+    fn test_reassigned_variables_are_not_propagated() {
+        let body = optimized_body("fn foo(x) { a = 5; a = x; b = a + 3; }");
+        assert!(matches!(&body[2], SimpleStatement(AssignmentExpr(_, value)) if matches!(value.as_ref(), BinaryExpr(_, _, _))));
+
+        // `a` may be modified by the loop
+        let body = optimized_body("fn foo(x) { a = 5; loop { b = a; a = x; break; } c = a; }");
+        match &body[1] {
+            Statement::Loop(loop_body) => assert!(matches!(loop_body.as_ref(), CompoundStatement(s) if s[0] == assignment("b", IdentExpr("a".into())))),
+            _ => panic!("Expected a loop")
+        }
+        assert_eq!(body[2], assignment("c", IdentExpr("a".into())));
+
+        // `a` is only modified in one of the branches
+        let body = optimized_body("fn foo(x) { a = 5; if (x) { a = 1; b = a; } c = a; }");
+        match &body[1] {
+            Statement::If(_, if_body, _) => assert!(matches!(if_body.as_ref(), CompoundStatement(s) if s[1] == assignment("b", ConstExpr(IntValue(1))))),
+            _ => panic!("Expected a if")
+        }
+        assert_eq!(body[2], assignment("c", IdentExpr("a".into())));
+    }
+
+    #[test]
+    fn test_failing_operations_are_not_folded() {
+        let body = optimized_body("fn foo() { a = 1 / 0; b = 2 ** 64; }");
+        assert!(matches!(&body[0], SimpleStatement(AssignmentExpr(_, value)) if matches!(value.as_ref(), BinaryExpr(_, _, _))));
+        assert!(matches!(&body[1], SimpleStatement(AssignmentExpr(_, value)) if matches!(value.as_ref(), BinaryExpr(_, _, _))));
+    }
+
+    /// Returns the statements of the function `main` of the optimized module
+    fn optimized_main(text: &str) -> Vec<Statement> {
+        let tokens = tokenize(text).unwrap();
+        let mut module = Parser::new(&tokens).parse_module().unwrap();
+        module.optimize();
+        match module.get_function("main".into()) {
+            Some(Declaration::Function(_, _, CompoundStatement(statements))) => statements.clone(),
+            _ => panic!("Expected a function main with a body")
+        }
+    }
+
+    #[test]
+    fn test_const_function_calls_are_folded() {
+        let body = optimized_main("const fn square(x) { return x * x; } fn main() { return square(4); }");
+        assert_eq!(body[0], Statement::Return(ConstExpr(IntValue(16))));
+
+        // Const functions can call each other, and the arguments can be folded first
+        let text = "\
+const fn square(x) { return x * x; }
+const fn sum_of_squares(a, b) { return square(a) + square(b); }
+fn main() { n = 1 + 2; return sum_of_squares(n, 4); }";
+        assert_eq!(optimized_main(text)[1], Statement::Return(ConstExpr(IntValue(25))));
+    }
+
+    #[test]
+    fn test_other_calls_are_not_folded() {
+        // A function that is not const
+        let body = optimized_main("fn square(x) { return x * x; } fn main() { return square(4); }");
+        assert!(matches!(&body[0], Statement::Return(FunctionCall(_, _, _))));
+
+        // A const function with a variable argument
+        let body = optimized_main("const fn square(x) { return x * x; } fn main(n) { return square(n); }");
+        assert!(matches!(&body[0], Statement::Return(FunctionCall(_, _, _))));
+
+        // A call that fails at runtime
+        let body = optimized_main("const fn first(l) { return l[0]; } fn main() { return first([]); }");
+        assert!(matches!(&body[0], Statement::Return(FunctionCall(_, _, _))));
+
+        // A variable that shadows the const function
+        let body = optimized_main("const fn f(x) { return x; } fn main() { f = len; return f([1, 2]); }");
+        assert!(matches!(&body[1], Statement::Return(FunctionCall(_, _, _))));
+    }
+
+    #[test]
+    fn test_impure_const_function() {
+        let tokens = tokenize("const fn greet(name) { print(name); }").unwrap();
+        assert!(matches!(Parser::new(&tokens).parse_declaration(), Err(ParserError::ImpureConstFunction)));
+        let tokens = tokenize("const fn apply() { return map(print, [1]); }").unwrap();
+        assert!(matches!(Parser::new(&tokens).parse_declaration(), Err(ParserError::ImpureConstFunction)));
+        let tokens = tokenize("const main() {}").unwrap();
+        assert!(Parser::new(&tokens).parse_declaration().is_err());
+
+        // A const function calling a function of the module that is not const
+        let tokens = tokenize("const fn uses_helper() { return helper(); } fn helper() { return 1; }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_module().err(), Some(ParserError::ConstFunctionCallsFunction("helper".to_string())));
+        let tokens = tokenize("fn helper() { return 1; } const fn apply() { return map(helper, [1]); }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_module().err(), Some(ParserError::ConstFunctionCallsFunction("helper".to_string())));
+    }
+
+    #[test]
+    fn test_const_calls_that_never_end_are_not_folded() {
+        let body = optimized_main("const fn spin() { loop { } } fn main() { return spin(); }");
+        assert!(matches!(&body[0], Statement::Return(FunctionCall(_, _, _))));
+
+        let body = optimized_main("const fn r(n) { return r(n + 1); } fn main() { return r(0); }");
+        assert!(matches!(&body[0], Statement::Return(FunctionCall(_, _, _))));
+
+        // The calls that end within the limits are still folded
+        let text = "\
+const fn spin() { loop { } }
+const fn count(n) { if (n == 0) { return 0; } return 1 + count(n - 1); }
+fn main() { a = spin(); return count(20); }";
+        assert_eq!(optimized_main(text)[1], Statement::Return(ConstExpr(IntValue(20))));
+    }
+}
@@ -1,27 +1,85 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashSet;
+
 use crate::ast::declaration::{Declaration, FnArg};
-use crate::ast::declaration::Declaration::Function;
-use crate::ast::expression::Expr;
-use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, CompareExpr, ConstExpr, FunctionCall, IdentExpr, List, ListAccess, NegExpr, ParenthesisExpr};
+use crate::analysis::{breaks_loop_statement_with_value, called_functions, is_pure, returns_outside_function};
+use crate::ast::declaration::Declaration::{Const, ConstFunction, Function};
+use crate::ast::expression::{Expr, Value};
+use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, CompareExpr, ConstExpr, FunctionCall, IdentExpr, List, ListAccess, ListSlice, LogicalExpr, NegExpr, ParenthesisExpr};
 use crate::ast::expression::Value::{BoolValue, IntValue, StringValue};
-use crate::ast::statement::Statement;
+use crate::ast::statement::{Pattern, Statement};
 use crate::ast::statement::Statement::{CompoundStatement, If};
-use crate::error::ParserError;
-use crate::error::ParserError::{ExpectedDifferentToken, UnknownSyntax, WrongFunctionArgumentList, WrongFunctionBody};
+use crate::error::{ParserError, TokenError};
+use crate::error::ParserError::{BreakValueInLoopStatement, ConstFunctionCallsFunction, ExpectedDifferentToken, ImpureConstFunction, InvalidToken, ReturnOutsideFunction, TokensNotParsed, UnexpectedToken, UnknownSyntax, WrongFunctionArgumentList, WrongFunctionBody};
 use crate::module::Module;
-use crate::token::{Op, Token};
+use crate::symbol::Symbol;
+use crate::token::{Comp, Logic, Op, Token, TokenStream};
+
+/// The tokens recorded as expected when any identifier or literal is expected, see
+/// `ParserError::UnexpectedToken`
+const ANY_IDENT: Token = Token::Ident(String::new());
+const ANY_INTEGER: Token = Token::Integer(0);
+const ANY_STRING: Token = Token::String(String::new());
+const ANY_CHAR: Token = Token::Char('\0');
+
+const COMPARISONS: [Token; 5] = [
+    Token::TokenComp(Comp::Equal),
+    Token::TokenComp(Comp::Lower),
+    Token::TokenComp(Comp::LowerEq),
+    Token::TokenComp(Comp::Higher),
+    Token::TokenComp(Comp::HigherEq),
+];
 
 /// A struct to contain data related to parsing
 ///
 /// Top-Down Parser
 pub struct Parser<'a> {
-    tokens: &'a Vec<Token>,
+    tokens: Cow<'a, [Token]>,
+    /// The stream that the tokens are read from, until it ends, see `Parser::from_stream`
+    stream: Option<TokenStream<'a>>,
+    /// The error that ended the stream, if any
+    stream_error: Option<TokenError>,
     index: usize,
+    /// Position of the furthest token that was inspected, where the parsing failed if the
+    /// tokens are not all consumed
+    furthest: Cell<usize>,
+    /// Tokens that were expected at `expected_at`, the furthest position where a token was expected
+    expected: Vec<Token>,
+    expected_at: usize,
 }
 
 /// Public API
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a Vec<Token>) -> Self {
-        Self { tokens, index: 0 }
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self::with_tokens(Cow::Borrowed(tokens))
+    }
+
+    /// Creates a parser that owns its tokens, so that it does not borrow from the caller
+    pub fn new_owned(tokens: Vec<Token>) -> Parser<'static> {
+        Parser::with_tokens(Cow::Owned(tokens))
+    }
+
+    fn with_tokens(tokens: Cow<'a, [Token]>) -> Self {
+        Self { tokens, stream: None, stream_error: None, index: 0, furthest: Cell::new(0), expected: vec![], expected_at: 0 }
+    }
+
+    /// Reports the furthest position reached by the parser, with the token found there and the
+    /// tokens that were expected instead. It explains why the tokens were not all parsed.
+    pub fn furthest_failure(&self) -> ParserError {
+        let position = self.furthest.get();
+        UnexpectedToken {
+            position,
+            found: self.tokens.get(position).cloned(),
+            expected: if self.expected_at == position { self.expected.clone() } else { vec![] },
+        }
+    }
+
+    /// Creates a parser that reads the tokens of the stream when it needs them.
+    /// The tokens that were read are kept, so that the parser can backtrack.
+    /// An invalid token ends the stream: parsing then fails with `ParserError::InvalidToken`.
+    pub fn from_stream(stream: TokenStream<'a>) -> Self {
+        Self { stream: Some(stream), ..Self::with_tokens(Cow::Owned(vec![])) }
     }
 
     /// An expression is something that is evaluated to something.
@@ -29,45 +87,153 @@ impl<'a> Parser<'a> {
     pub fn parse_expression(&mut self) -> Result<Expr, ParserError> {
         if let Some(assign) = self.parse_assignment_expr() {
             Ok(assign)
-        } else if let Some(tmp) = self.parse_comparison_expr() {
+        } else if let Some(tmp) = self.parse_or_expr() {
             Ok(tmp)
         } else {
             Err(UnknownSyntax)
         }
     }
 
-    pub fn parse_statements(&mut self) -> Vec<Statement> {
+    /// Parses statements until the end of the tokens
+    pub fn parse_statements(&mut self) -> Result<Vec<Statement>, ParserError> {
+        let statements = self.parse_statement_list();
+        self.with_stream_error(statements)
+    }
+
+    fn parse_statement_list(&mut self) -> Result<Vec<Statement>, ParserError> {
         let mut statements = vec![];
         while let Some(stm) = self.parse_one_statement() {
             statements.push(stm);
         }
-        statements
+        self.check_finished()?;
+        Ok(statements)
     }
 
-    pub fn parse_module(&mut self) -> Module {
+    /// Parses declarations until the end of the tokens
+    pub fn parse_module(&mut self) -> Result<Module, ParserError> {
+        let module = self.parse_declarations();
+        self.with_stream_error(module)
+    }
+
+    fn parse_declarations(&mut self) -> Result<Module, ParserError> {
         let mut declarations = vec![];
-        while let Ok(Some(ast)) = self.parse_declaration() {
+        while let Some(ast) = self.parse_declaration()? {
             declarations.push(ast);
         }
-        Module::new(declarations)
+        self.check_finished()?;
+        Self::check_const_functions(&declarations)?;
+        Ok(Module::new(declarations))
+    }
+
+    /// Fails when a `const fn` calls, or refers to, a function of the module that is not const,
+    /// since its result could not be evaluated by the optimizer
+    fn check_const_functions(declarations: &[Declaration]) -> Result<(), ParserError> {
+        let functions: HashSet<&str> = declarations.iter()
+            .filter(|declaration| matches!(declaration, Function(..)))
+            .map(Declaration::name)
+            .collect();
+        for declaration in declarations.iter().filter(|declaration| matches!(declaration, ConstFunction(..))) {
+            if let Some(called) = called_functions(declaration).into_iter().find(|called| functions.contains(called.as_str())) {
+                return Err(ConstFunctionCallsFunction(called));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with the error that ended the stream, which explains the result of the parsing
+    fn with_stream_error<T>(&mut self, result: Result<T, ParserError>) -> Result<T, ParserError> {
+        match self.stream_error.take() {
+            Some(error) => Err(InvalidToken(error)),
+            None => result,
+        }
+    }
+
+    /// Fails with the remaining tokens, when they are not all parsed
+    fn check_finished(&mut self) -> Result<(), ParserError> {
+        self.read_tokens(usize::MAX);
+        match self.tokens.get(self.index..) {
+            Some(remaining) if !remaining.is_empty() => Err(TokensNotParsed(remaining.to_vec())),
+            _ => Ok(())
+        }
     }
 }
 
 impl<'a> Parser<'a> {
+    /// Reads tokens from the stream, if any, until there are `len` tokens or the stream ends
+    fn read_tokens(&mut self, len: usize) {
+        let Some(stream) = &mut self.stream else { return };
+        while self.tokens.len() < len {
+            match stream.next() {
+                Some(Ok(token)) => self.tokens.to_mut().push(token),
+                Some(Err(error)) => {
+                    self.stream_error = Some(error);
+                    self.stream = None;
+                    return;
+                }
+                None => {
+                    self.stream = None;
+                    return;
+                }
+            }
+        }
+    }
+
     /// Inspect current token
-    fn peek(&self) -> Option<Token> {
-        self.tokens.get(self.index).map(|x| x.clone())
+    fn peek(&mut self) -> Option<&Token> {
+        self.furthest.set(self.furthest.get().max(self.index));
+        self.read_tokens(self.index + 1);
+        self.tokens.get(self.index)
+    }
+
+    /// Inspects the current token and the ones following it
+    fn peek_n<const N: usize>(&mut self) -> [Option<&Token>; N] {
+        self.furthest.set(self.furthest.get().max(self.index));
+        self.read_tokens(self.index + N);
+        std::array::from_fn(|i| self.tokens.get(self.index + i))
     }
 
-    fn is_finished(&self) -> bool {
+    /// Whether all the tokens have been parsed
+    pub fn is_finished(&mut self) -> bool {
+        self.read_tokens(self.index + 1);
         self.index == self.tokens.len()
     }
 
     /// Inspects current token and go forward
-    fn consume(&mut self) -> Option<Token> {
-        let tkn = self.tokens.get(self.index).map(|x| x.clone());
+    fn consume(&mut self) -> Option<&Token> {
+        self.furthest.set(self.furthest.get().max(self.index));
+        self.read_tokens(self.index + 1);
         self.index += 1;
-        tkn
+        self.tokens.get(self.index - 1)
+    }
+
+    /// Consumes the current token if it is the expected one.
+    /// Otherwise, the token is recorded as expected at the current position.
+    fn expect(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.index += 1;
+            return true;
+        }
+        self.record_expected(token);
+        false
+    }
+
+    /// Inspects the current token, which is matched against the alternatives.
+    /// They are recorded as expected at the current position, see `Parser::furthest_failure`.
+    fn peek_expecting(&mut self, alternatives: &[Token]) -> Option<&Token> {
+        for token in alternatives {
+            self.record_expected(token);
+        }
+        self.peek()
+    }
+
+    fn record_expected(&mut self, token: &Token) {
+        if self.index > self.expected_at {
+            self.expected.clear();
+            self.expected_at = self.index;
+        }
+        if self.index == self.expected_at && !self.expected.contains(token) {
+            self.expected.push(token.clone());
+        }
     }
 
     fn set_index(&mut self, index: usize) {
@@ -77,21 +243,90 @@ impl<'a> Parser<'a> {
 
     /// Parse any kind of declaration
     pub fn parse_declaration(&mut self) -> Result<Option<Declaration>, ParserError> {
+        if let Some(constant) = self.parse_global_constant()? {
+            return Ok(Some(constant));
+        }
+        if let Some(declaration) = self.parse_struct_declaration()? {
+            return Ok(Some(declaration));
+        }
         self.parse_one_function()
     }
 
-    /// Try to parse a function declaration
+    /// Try to parse a struct declaration: `struct Name { field, ... }`, with at least one field
+    fn parse_struct_declaration(&mut self) -> Result<Option<Declaration>, ParserError> {
+        if self.peek() != Some(&Token::Struct) {
+            return Ok(None);
+        }
+        self.index += 1;
+        let name = match self.consume() {
+            Some(Token::Ident(name)) => Symbol::intern(name),
+            _ => return Err(ExpectedDifferentToken("Expecting a name after `struct`"))
+        };
+        if !self.expect(&Token::LBrace) {
+            return Err(ExpectedDifferentToken("Expecting `{` after the name of the struct"));
+        }
+        let mut fields = vec![];
+        loop {
+            match self.consume() {
+                Some(Token::RBrace) => break,
+                Some(Token::Ident(field)) => {
+                    fields.push(Symbol::intern(field));
+                    match self.consume() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RBrace) => break,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            return Err(ExpectedDifferentToken("Expecting the names of the fields, separated by `,`"));
+        }
+        // `Name {}` is read as a variable followed by a block, so such a struct could never be built
+        if fields.is_empty() {
+            return Err(ExpectedDifferentToken("Expecting at least one field in the struct"));
+        }
+        Ok(Some(Declaration::Struct(name, fields)))
+    }
+
+    /// Try to parse a global constant: `const NAME = expr;`
+    fn parse_global_constant(&mut self) -> Result<Option<Declaration>, ParserError> {
+        if let [Some(Token::Const), Some(Token::Ident(name)), Some(Token::Equal)] = self.peek_n() {
+            let name = Symbol::intern(name);
+            self.index += 3;
+            return match (self.parse_or_expr(), self.consume()) {
+                (Some(value), Some(Token::SemiColon)) if returns_outside_function(&value) => Err(ReturnOutsideFunction),
+                (Some(value), Some(Token::SemiColon)) => Ok(Some(Const(name, value))),
+                _ => Err(ExpectedDifferentToken("Expecting an expression followed by `;` after `const NAME =`"))
+            };
+        }
+        Ok(None)
+    }
+
+    /// Try to parse a function declaration, optionally marked as `const`
     fn parse_one_function(&mut self) -> Result<Option<Declaration>, ParserError> {
-        if let Some(Token::Fn) = self.peek() {
+        let is_const = self.peek() == Some(&Token::Const);
+        if is_const {
             self.index += 1;
+        }
+        if self.expect(&Token::Fn) {
             if let Some(Token::Ident(name)) = self.peek() {
+                let name = Symbol::intern(name);
                 self.index += 1;
                 // Parse the list of arguments
                 match self.parse_function_argument_list() {
                     Ok(arguments) => {
                         // Parse the body of the function
                         if let Some(body) = self.parse_compound_statement() {
-                            return Ok(Some(Function(name, arguments, body)));
+                            if breaks_loop_statement_with_value(&body) {
+                                return Err(BreakValueInLoopStatement);
+                            }
+                            if !is_const {
+                                return Ok(Some(Function(name, arguments, body)));
+                            }
+                            if !is_pure(&body) {
+                                return Err(ImpureConstFunction);
+                            }
+                            return Ok(Some(ConstFunction(name, arguments, body)));
                         } else {
                             return Err(WrongFunctionBody);
                         }
@@ -102,19 +337,21 @@ impl<'a> Parser<'a> {
                 return Err(ExpectedDifferentToken("Expecting an indent after function declaration"));
             }
         }
+        if is_const {
+            return Err(ExpectedDifferentToken("Expecting `fn` after `const`"));
+        }
         Ok(None)
     }
 
     /// Try to parse the list of arguments in a function declaration
     fn parse_function_argument_list(&mut self) -> Result<Vec<FnArg>, ParserError> {
-        if let Some(Token::LPar) = self.peek() {
-            self.index += 1;
+        if self.expect(&Token::LPar) {
             let mut to_return = vec![];
             while let Some(token) = self.peek() {
                 match token {
                     Token::Ident(name) => {
+                        to_return.push(FnArg(Symbol::intern(name)));
                         self.index += 1;
-                        to_return.push(FnArg(name));
                     }
                     Token::RPar => {
                         self.index += 1;
@@ -136,30 +373,28 @@ impl<'a> Parser<'a> {
 
     /// Try to parse a list of expression contained between a left token and a right token
     /// and separated by a comma.
-    fn parse_expr_list(&mut self, left_matcher: fn(Option<Token>) -> bool, right_matcher: fn(Option<Token>) -> bool) -> Option<Vec<Expr>> {
-        if left_matcher(self.peek()) {
-            self.index += 1;
+    fn parse_expr_list(&mut self, left: &Token, right: &Token) -> Option<Vec<Expr>> {
+        if self.expect(left) {
             let mut to_return = vec![];
             // It's possible that a function has no argument at all
-            if right_matcher(self.peek()) {
-                self.index += 1;
+            if self.expect(right) {
                 return Some(to_return);
             }
             
-            // Otherwise, parse all the arguments
+            // Otherwise, parse all the arguments, which are separated by commas.
+            // A trailing comma is accepted after the last argument.
             while let Ok(expr) = self.parse_expression() {
                 to_return.push(expr);
-                match self.peek() {
-                    None => {}
-                    Some(Token::Comma) => self.index += 1,
-                    _ => {}
+                if !self.expect(&Token::Comma) {
+                    break;
                 }
-
-                if right_matcher(self.peek()) {
-                    self.index += 1;
+                if self.expect(right) {
                     return Some(to_return);
                 }
             }
+            if self.expect(right) {
+                return Some(to_return);
+            }
         }
         None
     }
@@ -172,35 +407,83 @@ impl<'a> Parser<'a> {
         if let Some(statement) = self.parse_loop_statement() {
             return Some(statement)
         }
+
+        if let Some(statement) = self.parse_repeat_statement() {
+            return Some(statement)
+        }
+
+        if let Some(statement) = self.parse_while_statement() {
+            return Some(statement)
+        }
+
+        if let Some(statement) = self.parse_do_while_statement() {
+            return Some(statement)
+        }
+
+        if let Some(statement) = self.parse_match_statement() {
+            return Some(statement)
+        }
+
+        if let Some(statement) = self.parse_local_function_statement() {
+            return Some(statement)
+        }
         
         // Parse return statement
-        if let Some(Token::Return) = self.peek() {
-            self.index += 1;
-            if let Ok(expr) = self.parse_expression() {
-                if let Some(Token::SemiColon) = self.peek() {
+        if self.expect(&Token::Return) {
+            // Without a value, `return` evaluates to `none`
+            match self.peek() {
+                Some(Token::SemiColon) => {
                     self.index += 1;
+                    return Some(Statement::Return(ConstExpr(Value::None)));
                 }
+                Some(Token::RBrace) => return Some(Statement::Return(ConstExpr(Value::None))),
+                _ => {}
+            }
+            if let Ok(expr) = self.parse_expression() {
+                self.expect(&Token::SemiColon);
                 return Some(Statement::Return(expr));
             }
             return None;
         }
         
-        // Parse break statement
-        if let Some(Token::Break) = self.peek() {
-            self.index += 1;
-            if let Some(Token::SemiColon) = self.peek() {
-                self.index += 1;
+        // Parse assert_eq statement
+        let checkpoint = self.index;
+        if self.expect(&Token::AssertEq) {
+            if let Ok(expected) = self.parse_expression() {
+                if self.expect(&Token::Comma) {
+                    if let Ok(actual) = self.parse_expression() {
+                        self.expect(&Token::SemiColon);
+                        return Some(Statement::AssertEq(expected, actual));
+                    }
+                }
             }
-            return Some(Statement::Break)
+            self.set_index(checkpoint);
+            return None;
+        }
+        self.set_index(checkpoint);
+
+        // Parse break statement
+        if self.expect(&Token::Break) {
+            let checkpoint = self.index;
+            let value = match self.parse_expression() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    self.set_index(checkpoint);
+                    None
+                }
+            };
+            self.expect(&Token::SemiColon);
+            return Some(Statement::Break(value))
         }
 
         // Parse simple statement
+        let checkpoint = self.index;
         if let Ok(expr) = self.parse_expression() {
-            if let Some(Token::SemiColon) = self.peek() {
-                self.index += 1;
+            if self.expect(&Token::SemiColon) {
                 return Some(Statement::SimpleStatement(expr));
             }
         }
+        self.set_index(checkpoint);
 
         // Parse compound statement
         if let Some(compound) = self.parse_compound_statement() {
@@ -210,22 +493,25 @@ impl<'a> Parser<'a> {
         None
     }
     
+    /// Matches "if (condition) { body }", optionally followed by "else { body }" or by
+    /// "else if ...", which is parsed as an `If` nested in the else branch.
     fn parse_if_statement(&mut self) -> Option<Statement> {
         let checkpoint = self.index;
-        if let Some(Token::If) = self.consume() {
-            if let Some(Token::LPar) = self.consume() {
-                if let Ok(expr) = self.parse_expression() {
-                    if let Some(Token::RPar) = self.consume() {
-                        if let Some(body) = self.parse_compound_statement() {
-                            // If there is an else statement, parse it here
-                            if let Some(Token::Else) = self.peek() {
-                                self.index += 1;
-                                if let Some(else_statement) = self.parse_one_statement() {
-                                    return Some(If(expr, Box::new(body), Some(Box::new(else_statement))));
-                                }
-                            } else {
-                                return Some(If(expr, Box::new(body), None));
+        if self.expect(&Token::If) && self.expect(&Token::LPar) {
+            if let Ok(expr) = self.parse_expression() {
+                if self.expect(&Token::RPar) {
+                    if let Some(body) = self.parse_compound_statement() {
+                        // If there is an else statement, parse it here
+                        if self.expect(&Token::Else) {
+                            let else_statement = match self.peek() {
+                                Some(Token::If) => self.parse_if_statement(),
+                                _ => self.parse_compound_statement()
+                            };
+                            if let Some(else_statement) = else_statement {
+                                return Some(If(expr, Box::new(body), Some(Box::new(else_statement))));
                             }
+                        } else {
+                            return Some(If(expr, Box::new(body), None));
                         }
                     }
                 }
@@ -237,7 +523,7 @@ impl<'a> Parser<'a> {
     
     fn parse_loop_statement(&mut self) -> Option<Statement> {
         let checkpoint = self.index;
-        if let Some(Token::Loop) = self.consume() {
+        if self.expect(&Token::Loop) {
             if let Some(body) = self.parse_compound_statement() {
                 return Some(Statement::Loop(Box::new(body)))
             }
@@ -246,19 +532,117 @@ impl<'a> Parser<'a> {
         None
     }
 
+    /// Matches "repeat expr { body }"
+    fn parse_repeat_statement(&mut self) -> Option<Statement> {
+        let checkpoint = self.index;
+        if self.expect(&Token::Repeat) {
+            if let Ok(count) = self.parse_expression() {
+                if let Some(body) = self.parse_compound_statement() {
+                    return Some(Statement::Repeat(count, Box::new(body)))
+                }
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "while (condition) { body }"
+    fn parse_while_statement(&mut self) -> Option<Statement> {
+        let checkpoint = self.index;
+        if self.expect(&Token::While) && self.expect(&Token::LPar) {
+            if let Ok(condition) = self.parse_expression() {
+                if self.expect(&Token::RPar) {
+                    if let Some(body) = self.parse_compound_statement() {
+                        return Some(Statement::While(condition, Box::new(body)))
+                    }
+                }
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "do { body } while (condition);"
+    fn parse_do_while_statement(&mut self) -> Option<Statement> {
+        let checkpoint = self.index;
+        if self.expect(&Token::Do) {
+            if let Some(body) = self.parse_compound_statement() {
+                if self.expect(&Token::While) && self.expect(&Token::LPar) {
+                    if let Ok(condition) = self.parse_expression() {
+                        if self.expect(&Token::RPar) && self.expect(&Token::SemiColon) {
+                            return Some(Statement::DoWhile(Box::new(body), condition))
+                        }
+                    }
+                }
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "match expr { pattern => { body } ... }"
+    fn parse_match_statement(&mut self) -> Option<Statement> {
+        let checkpoint = self.index;
+        if self.expect(&Token::Match) {
+            if let Ok(subject) = self.parse_expression() {
+                if self.expect(&Token::LBrace) {
+                    let mut arms = vec![];
+                    loop {
+                        if self.expect(&Token::RBrace) {
+                            return Some(Statement::Match(subject, arms));
+                        }
+                        match self.parse_match_arm() {
+                            Some(arm) => arms.push(arm),
+                            None => break
+                        }
+                    }
+                }
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "pattern => { body }", where the pattern is an integer or `_`
+    fn parse_match_arm(&mut self) -> Option<(Pattern, Statement)> {
+        let pattern = match self.consume() {
+            Some(Token::Integer(i)) => Pattern::Int(*i),
+            Some(Token::TokenOp(Op::Minus)) => match self.consume() {
+                Some(Token::Integer(i)) => Pattern::Int(-i),
+                _ => return None
+            },
+            Some(Token::Ident(name)) if name == "_" => Pattern::Wildcard,
+            _ => return None
+        };
+        if self.expect(&Token::FatArrow) {
+            return self.parse_compound_statement().map(|body| (pattern, body));
+        }
+        None
+    }
+
+    /// Matches "fn name(args) {body}" inside a function body
+    fn parse_local_function_statement(&mut self) -> Option<Statement> {
+        let checkpoint = self.index;
+        if let [Some(Token::Fn), Some(Token::Ident(_))] = self.peek_n() {
+            if let Ok(Some(Function(name, arguments, body))) = self.parse_one_function() {
+                return Some(Statement::LocalFn(name, arguments, Box::new(body)));
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
     /// Parse all the statements included inside a { block }
     fn parse_compound_statement(&mut self) -> Option<Statement> {
         let checkpoint = self.index;
-        if let Some(Token::LBrace) = self.peek() {
-            self.index += 1;
+        if self.expect(&Token::LBrace) {
             let mut statements = vec![];
             while let Some(stm) = self.parse_one_statement() {
                 statements.push(stm);
             }
             // Once there are no more statement being parsed, try to parse
             // a closing parenthesis.
-            if let Some(Token::RBrace) = self.peek() {
-                self.index += 1;
+            if self.expect(&Token::RBrace) {
                 return Some(CompoundStatement(statements));
             }
         }
@@ -266,13 +650,25 @@ impl<'a> Parser<'a> {
         None
     }
 
-    /// Matches "Ident = Something"
+    /// Matches "Ident = Something", or "Ident++" and "Ident--" which are desugared
+    /// into "Ident = Ident + 1" and "Ident = Ident - 1"
     fn parse_assignment_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
         if let Some(Token::Ident(name)) = self.consume() {
-            if let Some(Token::Equal) = self.consume() {
+            let name = Symbol::intern(name);
+            // The tokens of `i--` are also the beginning of `i - -1`, so the shorthand must
+            // be followed by the end of the expression
+            if let [Some(Token::TokenOp(op @ (Op::Plus | Op::Minus))), Some(Token::TokenOp(second)), None | Some(Token::SemiColon | Token::RPar | Token::RBrace | Token::RBracket | Token::Comma)]
+                = self.peek_n() {
+                if op == second {
+                    let value = BinaryExpr(Box::new(IdentExpr(name)), *op, Box::new(ConstExpr(IntValue(1))));
+                    self.index += 2;
+                    return Some(AssignmentExpr(name, Box::new(value)));
+                }
+            }
+            if self.expect(&Token::Equal) {
                 if let Ok(expr) = self.parse_expression() {
-                    return Some(AssignmentExpr(name.clone(), Box::new(expr)));
+                    return Some(AssignmentExpr(name, Box::new(expr)));
                 }
             }
         }
@@ -283,13 +679,38 @@ impl<'a> Parser<'a> {
     fn parse_function_call_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
         if let Some(Token::Ident(name)) = self.peek() {
+            let name = Symbol::intern(name);
             self.index += 1;
             // Try to parse an argument list
-            if let Some(arguments) = self.parse_expr_list(
-                |token| matches!(token, Some(Token::LPar)), 
-                |token| matches!(token, Some(Token::RPar))
-            ) {
-                return Some(FunctionCall(name, arguments));
+            if let Some(arguments) = self.parse_expr_list(&Token::LPar, &Token::RPar) {
+                // In a call, `name = value` binds the argument by its name, after the positional arguments
+                let mut positional = vec![];
+                let mut keywords = vec![];
+                for argument in arguments {
+                    match argument {
+                        AssignmentExpr(keyword, value) => keywords.push((keyword, *value)),
+                        argument if keywords.is_empty() => positional.push(argument),
+                        _ => {
+                            self.set_index(checkpoint);
+                            return None;
+                        }
+                    }
+                }
+                return Some(FunctionCall(name, positional, keywords));
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "fn(args) {body}", a function without a name
+    fn parse_closure_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        if self.expect(&Token::Fn) {
+            if let Ok(arguments) = self.parse_function_argument_list() {
+                if let Some(body) = self.parse_compound_statement() {
+                    return Some(Expr::Closure(arguments, Box::new(body)));
+                }
             }
         }
         self.set_index(checkpoint);
@@ -298,22 +719,99 @@ impl<'a> Parser<'a> {
 
     fn parse_list_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
-        if let Some(arguments) = self.parse_expr_list(
-            |token| matches!(token, Some(Token::LBracket)),
-            |token| matches!(token, Some(Token::RBracket))
-        ) {
+        if let Some(arguments) = self.parse_expr_list(&Token::LBracket, &Token::RBracket) {
             return Some(List(arguments));
         }
         self.set_index(checkpoint);
         None
     }
     
+    /// Matches "{ key: value, ... }"
+    fn parse_map_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        if self.expect(&Token::LBrace) {
+            let mut entries = vec![];
+            if self.expect(&Token::RBrace) {
+                return Some(Expr::Map(entries));
+            }
+            while let Ok(key) = self.parse_expression() {
+                if self.expect(&Token::Colon) {
+                    if let Ok(value) = self.parse_expression() {
+                        entries.push((key, value));
+                        match self.consume() {
+                            Some(Token::Comma) => continue,
+                            Some(Token::RBrace) => return Some(Expr::Map(entries)),
+                            _ => {}
+                        }
+                    }
+                }
+                break;
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "Name { field: value, ... }".
+    /// At least one field is required, so that an identifier followed by a block, as in
+    /// `repeat n { ... }`, is not parsed as a struct.
+    fn parse_struct_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        if let [Some(Token::Ident(name)), Some(Token::LBrace)] = self.peek_n() {
+            let name = Symbol::intern(name);
+            self.index += 2;
+            let mut fields = vec![];
+            while let [Some(Token::Ident(field)), Some(Token::Colon)] = self.peek_n() {
+                let field = Symbol::intern(field);
+                self.index += 2;
+                if let Ok(value) = self.parse_expression() {
+                    fields.push((field, value));
+                    match self.consume() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RBrace) => return Some(Expr::Struct(name, fields)),
+                        _ => {}
+                    }
+                }
+                break;
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "list[start:end]", where `list` is an identifier or a list literal
+    /// and both bounds are optional.
+    fn parse_list_slice_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        let list = if let Some(Token::Ident(name)) = self.peek() {
+            let name = Symbol::intern(name);
+            self.index += 1;
+            Some(IdentExpr(name))
+        } else {
+            self.parse_list_expr()
+        };
+        if let Some(list) = list {
+            if self.expect(&Token::LBracket) {
+                let start = self.parse_expression().ok().map(Box::new);
+                if self.expect(&Token::Colon) {
+                    let end = self.parse_expression().ok().map(Box::new);
+                    if self.expect(&Token::RBracket) {
+                        return Some(ListSlice(Box::new(list), start, end));
+                    }
+                }
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
     fn parse_list_access_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
         if let Some(Token::Ident(name)) = self.consume() {
-            if let Some(Token::LBracket) = self.consume() {
+            let name = Symbol::intern(name);
+            if self.expect(&Token::LBracket) {
                 if let Ok(expr) = self.parse_expression() {
-                    if let Some(Token::RBracket) = self.consume() {
+                    if self.expect(&Token::RBracket) {
                         return Some(ListAccess(name, Box::new(expr)))
                     }
                 }
@@ -323,15 +821,14 @@ impl<'a> Parser<'a> {
         None
     }
 
-    /// Parse boolean operators, such as '==', '<', '>'
-    fn parse_comparison_expr(&mut self) -> Option<Expr> {
+    /// Matches "And Expr || Or Expr" or "And Expr"
+    fn parse_or_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
-        if let Some(left) = self.parse_additive_expr() {
-            if let Some(Token::TokenComp(cmp)) = self.peek()
-            {
+        if let Some(left) = self.parse_and_expr() {
+            if let Some(&Token::TokenLogic(logic @ Logic::Or)) = self.peek_expecting(&[Token::TokenLogic(Logic::Or)]) {
                 self.index += 1;
-                if let Some(right) = self.parse_comparison_expr() {
-                    return Some(CompareExpr(Box::new(left), cmp, Box::new(right)));
+                if let Some(right) = self.parse_or_expr() {
+                    return Some(LogicalExpr(Box::new(left), logic, Box::new(right)));
                 }
             } else {
                 return Some(left);
@@ -341,11 +838,56 @@ impl<'a> Parser<'a> {
         None
     }
 
+    /// Matches "Comparison && And Expr" or "Comparison"
+    fn parse_and_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        if let Some(left) = self.parse_comparison_expr() {
+            if let Some(&Token::TokenLogic(logic @ Logic::And)) = self.peek_expecting(&[Token::TokenLogic(Logic::And)]) {
+                self.index += 1;
+                if let Some(right) = self.parse_and_expr() {
+                    return Some(LogicalExpr(Box::new(left), logic, Box::new(right)));
+                }
+            } else {
+                return Some(left);
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Parse boolean operators, such as '==', '<', '>'.
+    /// Chained comparisons `a < b < c` are kept together, so that `b` is only evaluated once
+    fn parse_comparison_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        let mut operands = vec![];
+        let mut comparisons = vec![];
+        while let Some(operand) = self.parse_additive_expr() {
+            operands.push(operand);
+            if let Some(&Token::TokenComp(cmp)) = self.peek_expecting(&COMPARISONS) {
+                self.index += 1;
+                comparisons.push(cmp);
+            } else {
+                break;
+            }
+        }
+        if operands.is_empty() || operands.len() == comparisons.len() {
+            self.set_index(checkpoint);
+            return None;
+        }
+        let mut operands = operands.into_iter();
+        let first = operands.next()?;
+        match comparisons.as_slice() {
+            [] => Some(first),
+            [cmp] => Some(CompareExpr(Box::new(first), *cmp, Box::new(operands.next()?))),
+            _ => Some(Expr::ChainedCompare(Box::new(first), comparisons.into_iter().zip(operands).collect())),
+        }
+    }
+
     /// Matches "Mul Expr +/- Mul Expr"
     fn parse_additive_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
         if let Some(left) = self.parse_multiplicative_expr() {
-            if let Some(Token::TokenOp(y @ Op::Plus) | Token::TokenOp(y @ Op::Minus)) = self.peek()
+            if let Some(&Token::TokenOp(y @ (Op::Plus | Op::Minus))) = self.peek_expecting(&[Token::TokenOp(Op::Plus), Token::TokenOp(Op::Minus)])
             {
                 self.index += 1;
                 if let Some(right) = self.parse_additive_expr() {
@@ -359,11 +901,11 @@ impl<'a> Parser<'a> {
         None
     }
 
-    /// Matches "Primary * Expr" or "Primary"
+    /// Matches "Power * Expr" or "Power"
     fn parse_multiplicative_expr(&mut self) -> Option<Expr> {
         let checkpoint = self.index;
-        if let Some(left) = self.parse_primary_expr() {
-            if let Some(Token::TokenOp(y @ Op::Times) | Token::TokenOp(y @ Op::Div)) = self.peek() {
+        if let Some(left) = self.parse_power_expr() {
+            if let Some(&Token::TokenOp(y @ (Op::Times | Op::Div))) = self.peek_expecting(&[Token::TokenOp(Op::Times), Token::TokenOp(Op::Div)]) {
                 self.index += 1;
                 if let Some(right) = self.parse_multiplicative_expr() {
                     return Some(BinaryExpr(Box::new(left), y, Box::new(right)));
@@ -376,23 +918,63 @@ impl<'a> Parser<'a> {
         None
     }
     
+    /// Matches "Field Access ** Power" or "Field Access"
+    /// The recursion on the right makes the operator right-associative.
+    fn parse_power_expr(&mut self) -> Option<Expr> {
+        let checkpoint = self.index;
+        if let Some(left) = self.parse_field_access_expr() {
+            if let Some(&Token::TokenOp(y @ Op::Pow)) = self.peek_expecting(&[Token::TokenOp(Op::Pow)]) {
+                self.index += 1;
+                if let Some(right) = self.parse_power_expr() {
+                    return Some(BinaryExpr(Box::new(left), y, Box::new(right)));
+                }
+            } else {
+                return Some(left);
+            }
+        }
+        self.set_index(checkpoint);
+        None
+    }
+
+    /// Matches "Primary.field", with any number of fields, or "Primary"
+    fn parse_field_access_expr(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_primary_expr()?;
+        loop {
+            self.record_expected(&Token::Dot);
+            match self.peek_n() {
+                [Some(Token::Dot), Some(Token::Ident(field))] => {
+                    let field = Symbol::intern(field);
+                    self.index += 2;
+                    expr = Expr::FieldAccess(Box::new(expr), field);
+                }
+                _ => return Some(expr),
+            }
+        }
+    }
+
     fn parse_constant_expr(&mut self) -> Option<Expr> {
-        if let Some(Token::Integer(value)) = self.peek() {
+        if let Some(&Token::Integer(value)) = self.peek_expecting(&[ANY_INTEGER]) {
             self.index += 1;
             return Some(ConstExpr(IntValue(value)));
         }
-        if let Some(Token::True) = self.peek() {
-            self.index += 1;
+        if self.expect(&Token::True) {
             return Some(ConstExpr(BoolValue(true)));
         }
-        if let Some(Token::False) = self.peek() {
-            self.index += 1;
+        if self.expect(&Token::False) {
             return Some(ConstExpr(BoolValue(false)));
         }
-        if let Some(Token::String(s)) = self.peek() {
+        if let Some(Token::String(s)) = self.peek_expecting(&[ANY_STRING]) {
+            let s = s.clone();
             self.index += 1;
             return Some(ConstExpr(StringValue(s)));
         }
+        if let Some(&Token::Char(c)) = self.peek_expecting(&[ANY_CHAR]) {
+            self.index += 1;
+            return Some(ConstExpr(Value::CharValue(c)));
+        }
+        if self.expect(&Token::None) {
+            return Some(ConstExpr(Value::None));
+        }
         None
     }
 
@@ -403,18 +985,39 @@ impl<'a> Parser<'a> {
             return Some(expr);
         }
 
+        // Closure
+        if let Some(expr) = self.parse_closure_expr() {
+            return Some(expr);
+        }
+
+        // Loop expression
+        if let Some(Statement::Loop(body)) = self.parse_loop_statement() {
+            return Some(Expr::Loop(body));
+        }
+
+        // Struct
+        if let Some(expr) = self.parse_struct_expr() {
+            return Some(expr);
+        }
+
         // Function call
         if let Some(expr) = self.parse_function_call_expr() {
             return Some(expr);
         }
 
+        // List slice
+        if let Some(expr) = self.parse_list_slice_expr() {
+            return Some(expr);
+        }
+
         // List access
         if let Some(expr) = self.parse_list_access_expr() {
             return Some(expr);
         }
 
         // Identifier
-        if let Some(Token::Ident(s)) = self.peek() {
+        if let Some(Token::Ident(s)) = self.peek_expecting(&[ANY_IDENT]) {
+            let s = Symbol::intern(s);
             self.index += 1;
             return Some(IdentExpr(s));
         }
@@ -424,11 +1027,16 @@ impl<'a> Parser<'a> {
             return Some(expr);
         }
 
+        // Map
+        if let Some(expr) = self.parse_map_expr() {
+            return Some(expr);
+        }
+
         // Parenthesis
         let checkpoint = self.index;
-        if let Some(Token::LPar) = self.consume() {
+        if self.expect(&Token::LPar) {
             if let Ok(expr) = self.parse_expression() {
-                if let Some(Token::RPar) = self.consume() {
+                if self.expect(&Token::RPar) {
                     return Some(ParenthesisExpr(Box::new(expr)));
                 }
             }
@@ -436,10 +1044,13 @@ impl<'a> Parser<'a> {
         self.set_index(checkpoint);
 
         // - Something
-        if let Some(Token::TokenOp(Op::Minus)) = self.peek() {
+        // A negative integer literal is a constant, so that it does not need to be evaluated
+        if let Some(Token::TokenOp(Op::Minus)) = self.peek_expecting(&[Token::TokenOp(Op::Minus)]) {
             self.index += 1;
-            if let Some(expr) = self.parse_primary_expr() {
-                return Some(NegExpr(Box::new(expr)));
+            match self.parse_field_access_expr() {
+                Some(ConstExpr(IntValue(i))) => return Some(ConstExpr(IntValue(-i))),
+                Some(expr) => return Some(NegExpr(Box::new(expr))),
+                None => self.set_index(checkpoint),
             }
         }
 
@@ -449,47 +1060,49 @@ impl<'a> Parser<'a> {
 }
 
 /// Parse a single expression
-pub fn parse_expression(tokens: &Vec<Token>) -> Result<Expr, ParserError> {
+pub fn parse_expression(tokens: &[Token]) -> Result<Expr, ParserError> {
     let mut parser = Parser::new(tokens);
     match parser.parse_expression() {
         Ok(ast) => {
-            if parser.is_finished() {
-                Ok(ast)
+            if !parser.is_finished() {
+                Err(parser.furthest_failure())
+            } else if returns_outside_function(&ast) {
+                Err(ReturnOutsideFunction)
             } else {
-                println!("Current ast = {ast:?}");
-                Err(ParserError::TokensNotParsed)
+                Ok(ast)
             }
         }
-        Err(err) => {
-            Err(err)
-        }
+        // The furthest failure tells what was expected instead
+        Err(UnknownSyntax) => Err(parser.furthest_failure()),
+        Err(err) => Err(err),
     }
 }
 
 /// Parse a list of statements
-pub fn parse_statements(tokens: &Vec<Token>) -> Vec<Statement> {
+pub fn parse_statements(tokens: &[Token]) -> Result<Vec<Statement>, ParserError> {
     let mut parser = Parser::new(tokens);
     parser.parse_statements()
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use crate::ast::declaration::Declaration;
+    use crate::ast::declaration::{Declaration, FnArg};
     use crate::ast::expression::{Expr, Value};
-    use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, ConstExpr, IdentExpr, List, ListAccess};
+    use crate::ast::expression::Expr::{AssignmentExpr, BinaryExpr, ConstExpr, FunctionCall, IdentExpr, List, ListAccess, ListSlice, LogicalExpr, NegExpr, ParenthesisExpr};
     use crate::ast::expression::Value::IntValue;
-    use crate::ast::statement::Statement;
-    use crate::ast::statement::Statement::SimpleStatement;
+    use crate::ast::statement::{Pattern, Statement};
+    use crate::ast::statement::Statement::{CompoundStatement, SimpleStatement};
+    use crate::error::{ParserError, TokenError};
     use crate::parser::{parse_expression, parse_statements, Parser};
     use crate::token::*;
 
     fn assert_ast(text: &str, expected: Expr) {
-        let tokens = tokenize(&text.to_string());
+        let tokens = tokenize(text);
         print!("Building AST for <input> = <{text}>:   ");
         if let Ok(ast) = parse_expression(&tokens.unwrap()) {
             assert_eq!(ast, expected);
         } else {
-            assert!(false);
+            panic!("The expression could not be parsed");
         }
     }
 
@@ -513,11 +1126,33 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_power_ast_expressions() {
+        // `**` is right-associative
+        assert_ast(
+            "2 ** 3 ** 2",
+            BinaryExpr(
+                Box::new(ConstExpr(IntValue(2))),
+                Op::Pow,
+                Box::new(BinaryExpr(Box::new(ConstExpr(IntValue(3))), Op::Pow, Box::new(ConstExpr(IntValue(2))))),
+            ),
+        );
+        // and has a higher precedence than `*`
+        assert_ast(
+            "2 * 3 ** 2",
+            BinaryExpr(
+                Box::new(ConstExpr(IntValue(2))),
+                Op::Times,
+                Box::new(BinaryExpr(Box::new(ConstExpr(IntValue(3))), Op::Pow, Box::new(ConstExpr(IntValue(2))))),
+            ),
+        );
+    }
+
     #[test]
     fn test_parse_single_statement() {
         let text = "a=1;".to_string();
         let tokens = tokenize(&text);
-        let statements = parse_statements(&tokens.unwrap());
+        let statements = parse_statements(&tokens.unwrap()).unwrap();
         assert_eq!(1, statements.len());
         println!("{statements:?}");
     }
@@ -526,7 +1161,7 @@ pub(crate) mod tests {
     fn test_parse_multiple_statements() {
         let text = "a=1;b=1;c=a+b;".to_string();
         let tokens = tokenize(&text);
-        let statements = parse_statements(&tokens.unwrap());
+        let statements = parse_statements(&tokens.unwrap()).unwrap();
         assert_eq!(3, statements.len());
         println!("{statements:#?}");
     }
@@ -544,8 +1179,7 @@ pub(crate) mod tests {
             assert!(matches!(statements[2], Statement::SimpleStatement(AssignmentExpr(_, _))));
             assert!(matches!(statements[3], Statement::SimpleStatement(BinaryExpr(_,Op::Plus, _))));
         } else {
-            println!("failed");
-            assert!(false);
+            panic!("The statements could not be parsed");
         }
     }
 
@@ -566,8 +1200,7 @@ pub(crate) mod tests {
             assert!(matches!(statements[2], Statement::SimpleStatement(AssignmentExpr(_, _))));
             assert!(matches!(statements[3], Statement::SimpleStatement(BinaryExpr(_,Op::Plus, _))));
         } else {
-            println!("failed");
-            assert!(false);
+            panic!("The statements could not be parsed");
         }
     }
 
@@ -584,8 +1217,7 @@ pub(crate) mod tests {
             assert!(matches!(statements[1], Statement::SimpleStatement(AssignmentExpr(_, _))));
             assert!(matches!(statements[2], Statement::Return(_)));
         } else {
-            println!("failed");
-            assert!(false);
+            panic!("The statements could not be parsed");
         }
     }
 
@@ -603,15 +1235,14 @@ fn my_func_name(first, second) {
                 println!("{name:?}");
                 println!("{args:?}");
                 println!("{body:?}");
-                assert_eq!(name, "my_func_name".to_string());
+                assert_eq!(name, "my_func_name");
                 assert_eq!(args.len(), 2);
-                assert_eq!(args[0].0, "first".to_string());
-                assert_eq!(args[1].0, "second".to_string());
+                assert_eq!(args[0].0, "first");
+                assert_eq!(args[1].0, "second");
             }
-            Ok(None) => assert!(false),
+            Ok(declaration) => panic!("Expected a function, got {declaration:?}"),
             Err(e) => {
-                println!("Error = {e:?}");
-                assert!(false);
+                panic!("Error = {e:?}");
             }
         }
         println!("{tokens:?}");
@@ -630,13 +1261,12 @@ fn my_func_name() {
                 println!("{name:?}");
                 println!("{args:?}");
                 println!("{body:?}");
-                assert_eq!(name, "my_func_name".to_string());
+                assert_eq!(name, "my_func_name");
                 assert_eq!(args.len(), 0);
             }
-            Ok(None) => assert!(false),
+            Ok(declaration) => panic!("Expected a function, got {declaration:?}"),
             Err(e) => {
-                println!("Error = {e:?}");
-                assert!(false);
+                panic!("Error = {e:?}");
             }
         }
         println!("{tokens:?}");
@@ -651,7 +1281,7 @@ fn my_func_name() {
         let text = get_simple_file();
         let tokens = tokenize(&text).unwrap();
         let mut parser = Parser::new(&tokens);
-        let file = parser.parse_module();
+        let file = parser.parse_module().unwrap();
         file.debug();
         assert_eq!(5, file.number_of_functions());
     }
@@ -659,15 +1289,15 @@ fn my_func_name() {
     #[test]
     fn test_parse_function_call_in_function() {
         let text = "foo(bar(1))";
-        let tokens = tokenize(&text.to_string());
+        let tokens = tokenize(text);
         let ast = parse_expression(&tokens.unwrap()).unwrap();
         match ast {
-            Expr::FunctionCall(name, args) => {
-                assert_eq!(name, "foo".to_string());
+            Expr::FunctionCall(name, args, _) => {
+                assert_eq!(name, "foo");
                 assert_eq!(1, args.len());
                 match &args[0] {
-                    Expr::FunctionCall(name, args) => {
-                        assert_eq!(*name, "bar".to_string());
+                    Expr::FunctionCall(name, args, _) => {
+                        assert_eq!(*name, "bar");
                         assert_eq!(1, args.len());
                         assert!(matches!(&args[0], ConstExpr(_)))
                     }
@@ -681,8 +1311,8 @@ fn my_func_name() {
     #[test]
     fn test_parse_simple_if() {
         let text = "if (1) {foo();}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         // Check that we parsed an IF statement without else clause
         assert!(matches!(ast[0], Statement::If(_, _, None)))
     }
@@ -690,8 +1320,8 @@ fn my_func_name() {
     #[test]
     fn test_parse_simple_if_else() {
         let text = "if (1) {foo();} else {bar();}";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         // Check that we parsed an IF statement without else clause
         println!("{ast:?}");
         assert!(matches!(ast[0], Statement::If(_, _, Some(_))))
@@ -700,18 +1330,13 @@ fn my_func_name() {
     #[test]
     fn test_parse_bool_value() {
         let text = "a = true;";
-        let tokens = tokenize(&text.to_string());
-        let ast = parse_statements(&tokens.unwrap());
+        let tokens = tokenize(text);
+        let ast = parse_statements(&tokens.unwrap()).unwrap();
         // Check that we parsed an IF statement without else clause
         println!("{ast:?}");
         match &ast[0] {
-            SimpleStatement(statement) => {
-                match statement {
-                    AssignmentExpr(_, expr) => {
-                        assert!(matches!(expr.as_ref(), ConstExpr(Value::BoolValue(_))))
-                    }
-                    _ => panic!("false")
-                }
+            SimpleStatement(AssignmentExpr(_, expr)) => {
+                assert!(matches!(expr.as_ref(), ConstExpr(Value::BoolValue(_))))
             }
             _ => panic!("false")
         }
@@ -720,7 +1345,7 @@ fn my_func_name() {
     #[test]
     fn test_parse_bool_comparison_equal() {
         let text = "a == true";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         assert!(matches!(ast, Expr::CompareExpr(_, Comp::Equal, _)))
@@ -729,7 +1354,7 @@ fn my_func_name() {
     #[test]
     fn test_parse_bool_comparison_higher() {
         let text = "a > 1";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         assert!(matches!(ast, Expr::CompareExpr(_, Comp::Higher, _)))
@@ -738,16 +1363,63 @@ fn my_func_name() {
     #[test]
     fn test_parse_bool_comparison_higher_eq() {
         let text = "a >= 1";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         assert!(matches!(ast, Expr::CompareExpr(_, Comp::HigherEq, _)))
     }
 
+    #[test]
+    fn test_parse_chained_comparison() {
+        let ident = |name: &str| IdentExpr(name.into());
+        let tokens = tokenize("a < b <= c").unwrap();
+        assert_eq!(parse_expression(&tokens).unwrap(), Expr::ChainedCompare(
+            Box::new(ident("a")),
+            vec![(Comp::Lower, ident("b")), (Comp::LowerEq, ident("c"))],
+        ));
+
+        let tokens = tokenize("a == b == c == d").unwrap();
+        assert_eq!(parse_expression(&tokens).unwrap(), Expr::ChainedCompare(
+            Box::new(ident("a")),
+            vec![(Comp::Equal, ident("b")), (Comp::Equal, ident("c")), (Comp::Equal, ident("d"))],
+        ));
+        // A single comparison is not a chain
+        let tokens = tokenize("a < b").unwrap();
+        assert_eq!(parse_expression(&tokens).unwrap(), Expr::CompareExpr(Box::new(ident("a")), Comp::Lower, Box::new(ident("b"))));
+
+        let tokens = tokenize("a < b <").unwrap();
+        assert!(parse_expression(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_logical_operators() {
+        let tokens = tokenize("a < 1 || b && c == 2").unwrap();
+        let ast = parse_expression(&tokens).unwrap();
+        match ast {
+            LogicalExpr(left, Logic::Or, right) => {
+                assert!(matches!(left.as_ref(), Expr::CompareExpr(_, Comp::Lower, _)));
+                assert!(matches!(right.as_ref(), LogicalExpr(_, Logic::And, _)));
+            }
+            _ => panic!("Expected a logical expression")
+        }
+    }
+
+    #[test]
+    fn test_parse_negative_numbers() {
+        assert_ast("-5", ConstExpr(IntValue(-5)));
+        assert_ast("[-1, -2, -3]", List(vec![ConstExpr(IntValue(-1)), ConstExpr(IntValue(-2)), ConstExpr(IntValue(-3))]));
+        assert_ast("foo(-5)", FunctionCall("foo".into(), vec![ConstExpr(IntValue(-5))], vec![]));
+        assert_ast("foo(1, -a)", FunctionCall("foo".into(), vec![ConstExpr(IntValue(1)), NegExpr(Box::new(IdentExpr("a".into())))], vec![]));
+        assert_ast("{-1: -2}", Expr::Map(vec![(ConstExpr(IntValue(-1)), ConstExpr(IntValue(-2)))]));
+        assert_ast("1 - -2", BinaryExpr(Box::new(ConstExpr(IntValue(1))), Op::Minus, Box::new(ConstExpr(IntValue(-2)))));
+        assert_ast("-(1)", NegExpr(Box::new(ParenthesisExpr(Box::new(ConstExpr(IntValue(1)))))));
+        assert!(parse_expression(&tokenize("[1, -]").unwrap()).is_err());
+    }
+
     #[test]
     fn test_parse_multiple_function_call() {
         let text = "foo(1) + bar(2)";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         assert!(matches!(ast, BinaryExpr(_, _, _)))
@@ -756,7 +1428,7 @@ fn my_func_name() {
     #[test]
     fn test_parse_simple_list() {
         let text = "[1,2,3]";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression();
         println!("{ast:?}");
@@ -764,14 +1436,32 @@ fn my_func_name() {
             List(values) => {
                 assert_eq!(3, values.len());
             }
-            _ => {assert!(false)}
+            ast => panic!("Expected a list, got {ast:?}")
         }
     }
     
+    #[test]
+    fn test_parse_nested_lists() {
+        let int = |i| ConstExpr(IntValue(i));
+        assert_ast("[]", List(vec![]));
+        assert_ast("[1]", List(vec![int(1)]));
+        assert_ast("[1,2,3]", List(vec![int(1), int(2), int(3)]));
+        assert_ast("[[1,2],[3]]", List(vec![List(vec![int(1), int(2)]), List(vec![int(3)])]));
+        assert!(parse_expression(&tokenize("[1,").unwrap()).is_err());
+        assert_ast("[1, 2, 3,]", List(vec![int(1), int(2), int(3)]));
+        assert_ast("[[1,],]", List(vec![List(vec![int(1)])]));
+        assert_ast("foo(1,)", FunctionCall("foo".into(), vec![int(1)], vec![]));
+        assert!(parse_expression(&tokenize("[,]").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("[1,,]").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("foo(,)").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("[1 2]").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("foo(1 2)").unwrap()).is_err());
+    }
+
     #[test]
     fn test_parse_list_with_expression() {
         let text = "[1+1,(2)*3,foo()]";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression();
         println!("{ast:?}");
@@ -779,68 +1469,422 @@ fn my_func_name() {
             List(values) => {
                 assert_eq!(3, values.len());
             }
-            _ => {assert!(false)}
+            ast => panic!("Expected a list, got {ast:?}")
         }
     }
     
+    #[test]
+    fn test_parse_map() {
+        let text = "{\"a\": 1, b: 1 + 1}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_expression().unwrap();
+        match ast {
+            Expr::Map(entries) => {
+                assert_eq!(2, entries.len());
+                assert!(matches!(entries[0], (ConstExpr(Value::StringValue(_)), ConstExpr(IntValue(1)))));
+                assert!(matches!(entries[1], (IdentExpr(_), BinaryExpr(_, Op::Plus, _))));
+            }
+            _ => panic!("Expected a map")
+        }
+
+        assert_ast("{}", Expr::Map(vec![]));
+        assert!(parse_expression(&tokenize("{1: 2").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("{1, 2}").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_blocks_are_not_parsed_as_maps() {
+        let text = "{} {a = 1;}";
+        let tokens = tokenize(text).unwrap();
+        let statements = parse_statements(&tokens).unwrap();
+        assert_eq!(2, statements.len());
+        assert!(matches!(&statements[0], Statement::CompoundStatement(s) if s.is_empty()));
+        assert!(matches!(&statements[1], Statement::CompoundStatement(s) if s.len() == 1));
+    }
+
     #[test]
     fn test_simple_list_access() {
         let text = "dog[0]";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         println!("{ast:?}");
         match ast { 
-            ListAccess(name, index) => {
+            ListAccess(_name, index) => {
                 assert!(matches!(index.as_ref(), &ConstExpr(_)))
             }
-            _ => assert!(false)
+            ast => panic!("Expected a list access, got {ast:?}")
         }
     }
     
+    #[test]
+    fn test_list_access_ast() {
+        assert_ast("a[2]", ListAccess("a".into(), Box::new(ConstExpr(IntValue(2)))));
+        assert_ast("a[0]", ListAccess("a".into(), Box::new(ConstExpr(IntValue(0)))));
+        assert!(parse_expression(&tokenize("a[]").unwrap()).is_err());
+        assert_ast(
+            "a[b[0]] + 1",
+            BinaryExpr(
+                Box::new(ListAccess("a".into(), Box::new(ListAccess("b".into(), Box::new(ConstExpr(IntValue(0))))))),
+                Op::Plus,
+                Box::new(ConstExpr(IntValue(1))),
+            ),
+        );
+        // A list literal in its own statement is not an access
+        assert_eq!(parse_statements(&tokenize("a; [2];").unwrap()).unwrap().len(), 2);
+        assert!(parse_expression(&tokenize("a[]").unwrap()).is_err());
+    }
+
     #[test]
     fn test_list_access() {
         let text = "dog[i]";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_expression().unwrap();
         println!("{ast:?}");
         match ast {
-            ListAccess(name, index) => {
+            ListAccess(_name, index) => {
                 assert!(matches!(index.as_ref(), &IdentExpr(_)))
             }
-            _ => assert!(false)
+            ast => panic!("Expected a list access, got {ast:?}")
         }
     }
     
+    #[test]
+    fn test_list_slice() {
+        let int = |i| Some(Box::new(ConstExpr(IntValue(i))));
+        assert_ast("dog[1:3]", ListSlice(Box::new(IdentExpr("dog".into())), int(1), int(3)));
+        assert_ast("dog[1:]", ListSlice(Box::new(IdentExpr("dog".into())), int(1), None));
+        assert_ast("dog[:3]", ListSlice(Box::new(IdentExpr("dog".into())), None, int(3)));
+        assert_ast("dog[:]", ListSlice(Box::new(IdentExpr("dog".into())), None, None));
+        assert_ast(
+            "[0,1,2,3][2:]",
+            ListSlice(
+                Box::new(List(vec![ConstExpr(IntValue(0)), ConstExpr(IntValue(1)), ConstExpr(IntValue(2)), ConstExpr(IntValue(3))])),
+                int(2),
+                None,
+            ),
+        );
+        // An access without `:` is still a list access
+        assert!(matches!(parse_expression(&tokenize("dog[1]").unwrap()), Ok(ListAccess(_, _))));
+    }
+
+    #[test]
+    fn test_parse_closure() {
+        let text = "f = fn(x, y) { return x + y; }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_expression().unwrap();
+        match ast {
+            AssignmentExpr(_, closure) => match closure.as_ref() {
+                Expr::Closure(args, body) => {
+                    assert_eq!(args, &vec![FnArg("x".into()), FnArg("y".into())]);
+                    assert!(matches!(body.as_ref(), CompoundStatement(s) if s.len() == 1));
+                }
+                _ => panic!("Expected a closure")
+            }
+            _ => panic!("Expected an assignment")
+        }
+        // A closure must have a body
+        assert!(parse_expression(&tokenize("fn(x)").unwrap()).is_err());
+        // A named function is a declaration, not an expression
+        assert!(parse_expression(&tokenize("fn foo(x) {}").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_local_function() {
+        let text = "fn outer() { fn helper(x) { return x; } return helper(1); }";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_declaration() {
+            Ok(Some(Declaration::Function(_, _, CompoundStatement(statements)))) => {
+                assert!(matches!(&statements[0], Statement::LocalFn(name, args, _) if name == "helper" && args.len() == 1));
+                assert!(matches!(&statements[1], Statement::Return(_)));
+            }
+            _ => panic!("Expected a function")
+        }
+    }
+
     #[test]
     fn test_simple_loop_parsing() {
         let text = "loop {i = i+1;}";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_one_statement().unwrap();
         println!("{ast:?}");
         assert!(matches!(ast, Statement::Loop(_)));
     }
 
+    #[test]
+    fn test_simple_repeat_parsing() {
+        let text = "repeat n + 1 {i = i+1;}";
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_one_statement().unwrap();
+        println!("{ast:?}");
+        assert!(matches!(ast, Statement::Repeat(BinaryExpr(_, Op::Plus, _), _)));
+    }
+
     #[test]
     fn test_simple_break_parsing() {
         let text = "break";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_one_statement().unwrap();
         println!("{ast:?}");
-        assert!(matches!(ast, Statement::Break));
+        assert!(matches!(ast, Statement::Break(None)));
     }
 
     #[test]
     fn test_simple_break_parsing_with_semicolon() {
         let text = "break;";
-        let tokens = tokenize(&text.to_string()).unwrap();
+        let tokens = tokenize(text).unwrap();
         let mut parser = Parser::new(&tokens);
         let ast = parser.parse_one_statement().unwrap();
         println!("{ast:?}");
-        assert!(matches!(ast, Statement::Break));
+        assert!(matches!(ast, Statement::Break(None)));
+    }
+
+    #[test]
+    fn test_owned_parser() {
+        // The parser does not borrow the tokens, so it can be returned
+        fn make_parser(text: &str) -> Parser<'static> {
+            Parser::new_owned(tokenize(text).unwrap())
+        }
+        let mut parser = make_parser("fn foo() { return 1; } fn main() { return foo(); }");
+        let module = parser.parse_module().unwrap();
+        assert_eq!(module.number_of_functions(), 2);
+        assert!(parser.is_finished());
+
+        let mut parser = Parser::from_stream(TokenStream::new("fn main() { return 1; }"));
+        assert_eq!(parser.parse_module().unwrap().number_of_functions(), 1);
+        let mut parser = Parser::from_stream(TokenStream::new("fn main() { $ }"));
+        assert!(matches!(parser.parse_module(), Err(ParserError::InvalidToken(TokenError::UnknownChar('$')))));
+
+        // The tokens are only read when they are needed, so a declaration is parsed before the
+        // invalid token that follows it is read
+        let mut parser = Parser::from_stream(TokenStream::new("fn main() { return 1; } $"));
+        assert!(matches!(parser.parse_declaration(), Ok(Some(Declaration::Function(..)))));
+        assert_eq!(parser.tokens.len(), 9);
+        assert!(matches!(parser.parse_module(), Err(ParserError::InvalidToken(TokenError::UnknownChar('$')))));
+    }
+
+    #[test]
+    fn test_while_parsing() {
+        let tokens = tokenize("while (i < n) { i = i + 1; }").unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_one_statement().unwrap();
+        assert!(matches!(ast, Statement::While(Expr::CompareExpr(_, _, _), _)));
+        assert!(parser.is_finished());
+
+        // The condition must be in parentheses, and the body must be a block
+        let tokens = tokenize("while i < n { i = i + 1; }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_one_statement(), None);
+        let tokens = tokenize("while (true) i = i + 1;").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_one_statement(), None);
+    }
+
+    #[test]
+    fn test_do_while_parsing() {
+        let tokens = tokenize("do { i = i + 1; } while (i < n);").unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_one_statement().unwrap();
+        assert!(matches!(ast, Statement::DoWhile(_, Expr::CompareExpr(_, _, _))));
+        assert!(parser.is_finished());
+
+        // The body must be a block, and the statement ends with a semicolon
+        let tokens = tokenize("do i = i + 1; while (i < n);").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_one_statement(), None);
+        let tokens = tokenize("do { i = i + 1; } while (i < n)").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_one_statement(), None);
+        let tokens = tokenize("do { i = i + 1; }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_one_statement(), None);
+    }
+
+    #[test]
+    fn test_increment_parsing() {
+        let increment = |name: &str, op| AssignmentExpr(
+            name.into(),
+            Box::new(BinaryExpr(Box::new(IdentExpr(name.into())), op, Box::new(ConstExpr(IntValue(1))))),
+        );
+        let tokens = tokenize("i++").unwrap();
+        assert_eq!(parse_expression(&tokens), Ok(increment("i", Op::Plus)));
+        let tokens = tokenize("count--").unwrap();
+        assert_eq!(parse_expression(&tokens), Ok(increment("count", Op::Minus)));
+        let tokens = tokenize("while (i < 3) { i++; }").unwrap();
+        assert!(matches!(Parser::new(&tokens).parse_one_statement(), Some(Statement::While(..))));
+        // Followed by an operand, `--` is a subtraction of a negative value
+        let tokens = tokenize("a--1").unwrap();
+        assert_eq!(parse_expression(&tokens), Ok(BinaryExpr(Box::new(IdentExpr("a".into())), Op::Minus, Box::new(ConstExpr(IntValue(-1))))));
+        // Only a variable can be incremented
+        assert!(parse_expression(&tokenize("1++").unwrap()).is_err());
+        assert!(parse_expression(&tokenize("++i").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_break_value_parsing() {
+        let tokens = tokenize("break 42;").unwrap();
+        let ast = Parser::new(&tokens).parse_one_statement().unwrap();
+        assert_eq!(ast, Statement::Break(Some(Expr::ConstExpr(Value::IntValue(42)))));
+
+        let tokens = tokenize("x = loop { break x + 1; };").unwrap();
+        let mut parser = Parser::new(&tokens);
+        let ast = parser.parse_one_statement().unwrap();
+        match ast {
+            SimpleStatement(AssignmentExpr(name, value)) => {
+                assert_eq!(name, "x");
+                assert!(matches!(*value, Expr::Loop(_)));
+            }
+            _ => panic!("Expected an assignment, got {ast:?}")
+        }
+        assert!(parser.is_finished());
+
+        // Only a `loop` can be given a value
+        for text in ["fn f() { while (true) { break 42; } }", "fn f() { repeat 2 { if (true) { break 1; } } }", "fn f() { do { break 1; } while (true); }"] {
+            let tokens = tokenize(text).unwrap();
+            assert!(matches!(Parser::new(&tokens).parse_declaration(), Err(ParserError::BreakValueInLoopStatement)), "{text}");
+        }
+        for text in ["fn f() { while (true) { x = loop { break 1; }; break; } }", "fn f() { repeat 2 { g = fn() { loop { break 1; } }; } }", "fn f() { loop { break 1; } }"] {
+            let tokens = tokenize(text).unwrap();
+            assert!(Parser::new(&tokens).parse_declaration().is_ok(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_return_without_value_parsing() {
+        let statements = parse_statements(&tokenize("{ if (done) { return; } return }").unwrap()).unwrap();
+        match &statements[..] {
+            [CompoundStatement(statements)] => {
+                assert!(matches!(&statements[0], Statement::If(_, body, None) if matches!(body.as_ref(), CompoundStatement(s) if s[0] == Statement::Return(ConstExpr(Value::None)))));
+                assert_eq!(statements[1], Statement::Return(ConstExpr(Value::None)));
+            }
+            _ => panic!("Expected a single block")
+        }
+    }
+
+    #[test]
+    fn test_match_parsing() {
+        let tokens = tokenize("match x { 1 => { a = 1; } -2 => {} _ => { return 0; } }").unwrap();
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_one_statement() {
+            Some(Statement::Match(IdentExpr(name), arms)) => {
+                assert_eq!(name, "x");
+                let patterns: Vec<Pattern> = arms.into_iter().map(|(pattern, _)| pattern).collect();
+                assert_eq!(patterns, vec![Pattern::Int(1), Pattern::Int(-2), Pattern::Wildcard]);
+            }
+            statement => panic!("Expected a match statement, got {statement:?}")
+        }
+        assert!(parser.is_finished());
+
+        // Patterns are only integers or `_`, and bodies are blocks
+        for text in ["match x { y => {} }", "match x { 1 => a = 1; }", "match x { 1 {} }", "match x { 1 => {}"] {
+            let tokens = tokenize(text).unwrap();
+            assert_eq!(Parser::new(&tokens).parse_one_statement(), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_keyword_arguments_parsing() {
+        let ast = parse_expression(&tokenize("foo(1, b = 2, a = x)").unwrap()).unwrap();
+        match ast {
+            Expr::FunctionCall(name, args, keywords) => {
+                assert_eq!(name, "foo");
+                assert_eq!(args, vec![ConstExpr(IntValue(1))]);
+                assert_eq!(keywords, vec![("b".into(), ConstExpr(IntValue(2))), ("a".into(), IdentExpr("x".into()))]);
+            }
+            _ => panic!("Expected a function call")
+        }
+
+        // Positional arguments can't follow keyword arguments
+        assert!(parse_expression(&tokenize("foo(b = 2, 1)").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_struct_parsing() {
+        let tokens = tokenize("struct Point { x, y }").unwrap();
+        assert!(matches!(
+            Parser::new(&tokens).parse_declaration(),
+            Ok(Some(Declaration::Struct(name, fields))) if name == "Point" && fields == ["x", "y"]
+        ));
+        let tokens = tokenize("struct Point { x y }").unwrap();
+        assert!(Parser::new(&tokens).parse_declaration().is_err());
+        let tokens = tokenize("struct Empty {}").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_declaration().err(), Some(ParserError::ExpectedDifferentToken("Expecting at least one field in the struct")));
+
+        assert_ast("Point { x: 1, y: a }", Expr::Struct("Point".into(), vec![
+            ("x".into(), ConstExpr(IntValue(1))),
+            ("y".into(), IdentExpr("a".into())),
+        ]));
+        assert_ast("-a.b", Expr::NegExpr(Box::new(Expr::FieldAccess(Box::new(IdentExpr("a".into())), "b".into()))));
+    }
+
+    #[test]
+    fn test_furthest_failure() {
+        let tokens = tokenize("fn main() { a = (1 + 2; return a; }").unwrap();
+        let mut parser = Parser::new(&tokens);
+        assert_eq!(parser.parse_module().err(), Some(ParserError::WrongFunctionBody));
+        assert!(!parser.is_finished());
+        match parser.furthest_failure() {
+            ParserError::UnexpectedToken { position: 11, found: Some(Token::SemiColon), expected } => {
+                assert!(expected.contains(&Token::RPar) && expected.contains(&Token::TokenOp(Op::Plus)));
+            }
+            failure => panic!("Expected an unexpected `;`, got {failure:?}")
+        }
+
+        let tokens = tokenize("fn main() { if (a) { return 1; } else return 2; }").unwrap();
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse_module().is_err());
+        assert_eq!(parser.furthest_failure(), ParserError::UnexpectedToken { position: 15, found: Some(Token::Return), expected: vec![Token::LBrace] });
+    }
+
+    #[test]
+    fn test_leftover_tokens() {
+        let tokens = tokenize("fn main() { return 1; } }").unwrap();
+        assert_eq!(Parser::new(&tokens).parse_module().err(), Some(ParserError::TokensNotParsed(vec![Token::RBrace])));
+
+        let tokens = tokenize("a = 1; ) b").unwrap();
+        assert_eq!(
+            parse_statements(&tokens).err(),
+            Some(ParserError::TokensNotParsed(vec![Token::RPar, Token::Ident("b".to_string())]))
+        );
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let text = "if (a == 1) { return 1; } else if (a == 2) { return 2; } else if (a == 3) { return 3; } else { return 0; }";
+        let statements = parse_statements(&tokenize(text).unwrap()).unwrap();
+        assert_eq!(statements.len(), 1);
+        let mut branch = &statements[0];
+        for _ in 0..3 {
+            match branch {
+                Statement::If(_, body, Some(else_statement)) => {
+                    assert!(matches!(body.as_ref(), CompoundStatement(_)));
+                    branch = else_statement;
+                }
+                _ => panic!("Expected an if with an else branch, got {branch:?}")
+            }
+        }
+        assert!(matches!(branch, CompoundStatement(_)));
+
+        // The else branch must be a block or another if
+        assert!(parse_statements(&tokenize("if (a) { b; } else b;").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_field_access_is_left_associative() {
+        assert_ast("a.b.c", Expr::FieldAccess(
+            Box::new(Expr::FieldAccess(Box::new(IdentExpr("a".into())), "b".into())),
+            "c".into(),
+        ));
+        assert_ast("a.b + c.d", BinaryExpr(
+            Box::new(Expr::FieldAccess(Box::new(IdentExpr("a".into())), "b".into())),
+            Op::Plus,
+            Box::new(Expr::FieldAccess(Box::new(IdentExpr("c".into())), "d".into())),
+        ));
+        assert_ast("f(x).y", Expr::FieldAccess(
+            Box::new(Expr::FunctionCall("f".into(), vec![IdentExpr("x".into())], vec![])),
+            "y".into(),
+        ));
     }
-    
 }
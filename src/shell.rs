@@ -1,21 +1,24 @@
-use std::collections::HashMap;
 use std::io::{stdin, stdout, Write};
+use std::time::Instant;
 
 use colored::Colorize;
 
 use crate::ast::expression::*;
-use crate::error::EvalError;
-use crate::parser::parse_expression;
-use crate::token::tokenize;
+use crate::error::{EvalError, InterpreterError};
+use crate::filesystem::OsFileSystem;
+use crate::interpreter::Interpreter;
 
 pub struct Shell {
-    vars: HashMap<String, Value>,
+    interpreter: Interpreter,
+    /// Whether the duration of each evaluation is printed after its result
+    timed: bool,
 }
 
 impl Shell {
     pub fn new() -> Self {
         Self {
-            vars: HashMap::new(),
+            interpreter: Interpreter::new(),
+            timed: false,
         }
     }
 
@@ -36,32 +39,183 @@ impl Shell {
                 s.pop();
             }
 
-            match s.as_str() {
-                "vars" => println!("{:?}", self.vars),
-                _ => self.interpret(&s)
+            self.execute(&s);
+        }
+    }
+
+    /// Executes a line of the shell: either a command, or an expression to evaluate
+    fn execute(&mut self, line: &str) {
+        match line.split_once(' ') {
+            Some((":save", path)) => self.save_variables(path.trim()),
+            Some((":load-vars", path)) => self.load_variables(path.trim()),
+            Some((":time", toggle)) => match toggle.trim() {
+                "on" => self.timed = true,
+                "off" => self.timed = false,
+                _ => println!("{} use `:time on` or `:time off`", "Error:".red()),
+            }
+            _ if line == "vars" => {
+                for line in self.format_vars(true) {
+                    println!("{line}");
+                }
+            }
+            _ if line == ":clear" => {
+                self.interpreter.clear();
+                println!("Variables and functions cleared");
             }
+            _ => self.interpret(line)
         }
     }
 
-    fn eval(&mut self, ast: &Expr) -> Result<Value, EvalError> {
-        ast.eval(&mut self.vars, None)
+    fn interpret(&mut self, text: &str) {
+        let start = Instant::now();
+        let result = self.interpreter.eval_str(text);
+        let elapsed = start.elapsed();
+        match result {
+            Ok(Value::None) => {}
+            Ok(value) => println!("{value}"),
+            Err(error) => println!("{}", Self::describe_error(&error)),
+        }
+        if self.timed {
+            println!("{}", format!("Evaluated in {elapsed:?}").dimmed());
+        }
     }
 
-    fn interpret(&mut self, text: &String) {
-        match tokenize(text) {
-            Ok(tokens) => {
-                match parse_expression(&tokens) {
-                    Ok(ast) => {
-                        match self.eval(&ast) {
-                            Ok(Value::None) => {}
-                            Ok(value) => println!("{value}"),
-                            Err(e) => println!("{} {e:?}", "Error while evaluating: ".red()),
-                        }
-                    }
-                    Err(e) => println!("{} {e:?}", "Error while parsing: ".red()),
+    /// Returns one line `name = value` per variable, sorted by name and aligned on the `=`,
+    /// which is dimmed when `colored` is set
+    fn format_vars(&self, colored: bool) -> Vec<String> {
+        let equal = if colored { "=".dimmed().to_string() } else { "=".to_string() };
+        let mut variables: Vec<(String, Value)> = self.interpreter.variables().variables().into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let width = variables.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        variables.into_iter()
+            .map(|(name, value)| format!("{name:width$} {equal} {value}"))
+            .collect()
+    }
+
+    fn save_variables(&self, path: &str) {
+        match self.interpreter.save_variables(&OsFileSystem, path) {
+            Ok(skipped) => {
+                for name in skipped {
+                    println!("{} `{name}` holds a function, it was not saved", "Warning:".yellow());
                 }
             }
-            Err(err) => println!("{} {err:?}", "Error while tokenizing: ".red())
+            Err(error) => println!("{}", Self::describe_error(&error)),
+        }
+    }
+
+    fn load_variables(&mut self, path: &str) {
+        match self.interpreter.load_variables(&OsFileSystem, path) {
+            Ok(count) => println!("{count} variables loaded"),
+            Err(error) => println!("{}", Self::describe_error(&error)),
+        }
+    }
+
+    /// Prints the value of each expression statement of `main` when running a file,
+    /// see [`Interpreter::set_verbose`]
+    pub fn set_verbose(&mut self, enabled: bool) {
+        self.interpreter.set_verbose(enabled);
+    }
+
+    /// Runs the `main` function of the module in the file.
+    /// Returns the text to print for its result, see [`Shell::output`].
+    pub fn run_file(&mut self, path: &str, args: Vec<String>) -> Result<Option<String>, String> {
+        Self::output(self.interpreter.run_file(&OsFileSystem, path, args))
+    }
+
+    /// Evaluates a single expression.
+    /// Returns the text to print for its value, see [`Shell::output`].
+    pub fn eval_once(&mut self, text: &str) -> Result<Option<String>, String> {
+        Self::output(self.interpreter.eval_str(text))
+    }
+
+    /// Returns the printed value, or nothing when there is no value, or the description of the error
+    fn output(result: Result<Value, InterpreterError>) -> Result<Option<String>, String> {
+        match result {
+            Ok(Value::None) => Ok(None),
+            Ok(value) => Ok(Some(value.to_string())),
+            Err(error) => Err(Self::describe_error(&error)),
+        }
+    }
+
+    fn describe_error(error: &InterpreterError) -> String {
+        match error {
+            InterpreterError::Token(err) => format!("{} {err:?}", "Error while tokenizing:".red()),
+            InterpreterError::Parser(e) => format!("{} {e:?}", "Error while parsing:".red()),
+            InterpreterError::Eval(EvalError::WithBacktrace { inner, stack }) => {
+                format!("{} {inner:?} in {}", "Error while evaluating:".red(), stack.join(" -> "))
+            }
+            InterpreterError::Eval(e) => format!("{} {e:?}", "Error while evaluating:".red()),
         }
     }
 }
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shell::Shell;
+
+    #[test]
+    fn test_eval_once() {
+        let mut shell = Shell::new();
+        assert_eq!(shell.eval_once("1 + 2 * 3"), Ok(Some("7".to_string())));
+        assert_eq!(shell.eval_once("[1, 2] == [1, 2]"), Ok(Some("true".to_string())));
+        assert_eq!(shell.eval_once("none"), Ok(None));
+        assert!(shell.eval_once("1 +").unwrap_err().contains("Error while parsing"));
+        assert!(shell.eval_once("a").unwrap_err().contains("UnknownVariable"));
+
+        shell.interpreter.run_module("fn foo() { return a; } fn main() { return 1; }").unwrap();
+        assert!(shell.eval_once("foo()").unwrap_err().ends_with("UnknownVariable(\"a\")"));
+        shell.interpreter.run_module("fn bar() { return a; } fn foo() { return bar(); } fn main() { return 1; }").unwrap();
+        assert!(shell.eval_once("foo()").unwrap_err().ends_with("UnknownVariable(\"a\") in foo -> bar"));
+    }
+
+    #[test]
+    fn test_format_vars() {
+        let mut shell = Shell::new();
+        assert!(shell.format_vars(false).is_empty());
+        shell.execute("total = 1 + 2");
+        shell.execute("b = [1, 2]");
+        shell.execute("name = \"abc\"");
+        assert_eq!(shell.format_vars(false), vec![
+            "b     = [1, 2]",
+            "name  = abc",
+            "total = 3",
+        ]);
+    }
+
+    #[test]
+    fn test_time_toggle() {
+        let mut shell = Shell::new();
+        assert!(!shell.timed);
+        shell.execute(":time on");
+        assert!(shell.timed);
+        shell.execute("1 + 1");
+        shell.execute(":time maybe");
+        assert!(shell.timed);
+        shell.execute(":time off");
+        assert!(!shell.timed);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut shell = Shell::new();
+        shell.execute("a = 1");
+        shell.execute("b = [a]");
+        assert_eq!(shell.interpreter.variables().variables().len(), 2);
+        shell.execute(":clear");
+        assert!(shell.interpreter.variables().variables().is_empty());
+        assert!(shell.eval_once("a").is_err());
+    }
+
+    #[test]
+    fn test_run_file() {
+        let mut shell = Shell::new();
+        assert_eq!(shell.run_file("TestData/simple_file.txt", vec![]), Ok(Some("8".to_string())));
+        assert!(shell.run_file("TestData/missing.txt", vec![]).unwrap_err().contains("IoError"));
+    }
+}
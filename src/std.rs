@@ -1,37 +1,169 @@
-use std::collections::HashMap;
-use crate::ast::expression::{Expr, Value};
+use std::cmp::Ordering;
+use std::io::{stdin, stdout, BufRead, Write};
+use std::rc::Rc;
+
+use crate::ast::expression::Value;
 use crate::error::EvalError;
+use crate::filesystem::{FileSystem, OsFileSystem};
+use crate::module::Module;
 
 /// Standard Library
 pub struct Std;
 
-const PRINT: &'static str = "print";
-const LEN: &'static str = "len";
+const PRINT: &str = "print";
+const PRINT_INLINE: &str = "print_inline";
+const LEN: &str = "len";
+const TRUTHY: &str = "truthy";
+const CHUNKS: &str = "chunks";
+const WINDOWS: &str = "windows";
+const TO_JSON: &str = "to_json";
+const FROM_JSON: &str = "from_json";
+const MAP: &str = "map";
+const READ_FILE: &str = "read_file";
+const WRITE_FILE: &str = "write_file";
+const SPLIT_LINES: &str = "split_lines";
+const GROUP_DIGITS: &str = "group_digits";
+const STR: &str = "str";
+const INPUT: &str = "input";
+const MIN: &str = "min";
+const MAX: &str = "max";
+const ASSERT: &str = "assert";
+const TYPE: &str = "type";
+const ABS: &str = "abs";
+const KEYS: &str = "keys";
+const VALUES: &str = "values";
+const CONTAINS: &str = "contains";
+const REVERSE: &str = "reverse";
+const SORT: &str = "sort";
+const SPLIT: &str = "split";
+const JOIN: &str = "join";
+const INT: &str = "int";
+const REDUCE: &str = "reduce";
+const FILTER: &str = "filter";
+const ERROR: &str = "error";
 
 impl Std {
-    pub fn is_in_standard_lib(name: &String) -> bool {
-        if let PRINT | LEN = name.as_str() {
+    pub fn is_in_standard_lib(name: &str) -> bool {
+        if let PRINT | PRINT_INLINE | LEN | TRUTHY | CHUNKS | WINDOWS | TO_JSON | FROM_JSON | MAP
+            | READ_FILE | WRITE_FILE | SPLIT_LINES | GROUP_DIGITS | STR | INPUT | MIN | MAX | ASSERT
+            | TYPE | ABS | KEYS | VALUES | CONTAINS | REVERSE | SORT
+            | SPLIT | JOIN | INT | REDUCE | FILTER | ERROR = name {
             return true
         }
         false
     }
 
-    pub fn eval(name: &String, args: &Vec<Value>) -> Result<Value, EvalError> {
-        match name.as_str() {
+    /// Returns whether the function reads or writes outside of the program, so that its
+    /// result depends on more than its arguments
+    pub fn is_impure(name: &str) -> bool {
+        matches!(name, PRINT | PRINT_INLINE | INPUT | READ_FILE | WRITE_FILE)
+    }
+
+    /// Evaluates a function of the standard library.
+    /// The module is used by the functions that call back user-defined functions.
+    pub fn eval(name: &str, args: &[Value], module: Option<&Module>) -> Result<Value, EvalError> {
+        match name {
             PRINT => Self::print(args),
+            PRINT_INLINE => return Self::print_inline(&mut stdout(), args),
             LEN => return Self::get_list_length(args),
+            TRUTHY => return Self::truthy(args),
+            CHUNKS => return Self::chunks(args),
+            WINDOWS => return Self::windows(args),
+            TO_JSON => return Self::to_json(args),
+            FROM_JSON => return Self::from_json(args),
+            MAP => return Self::map(args, module),
+            REDUCE => return Self::reduce(args, module),
+            FILTER => return Self::filter(args, module),
+            READ_FILE => return Self::read_file(&OsFileSystem, args),
+            WRITE_FILE => return Self::write_file(&OsFileSystem, args),
+            SPLIT_LINES => return Self::split_lines(args),
+            GROUP_DIGITS => return Self::group_digits(args),
+            STR => return Self::str(args),
+            INPUT => return Self::input(&mut stdin().lock(), &mut stdout(), args),
+            MIN => return Self::extremum(args, Ordering::Less),
+            MAX => return Self::extremum(args, Ordering::Greater),
+            ASSERT => return Self::assert(args),
+            ERROR => return Self::error(args),
+            TYPE => return Self::type_of(args),
+            ABS => return Self::abs(args),
+            KEYS => return Self::keys(args),
+            VALUES => return Self::values(args),
+            CONTAINS => return Self::contains(args),
+            REVERSE => return Self::reverse(args),
+            SORT => return Self::sort(args),
+            SPLIT => return Self::split(args),
+            JOIN => return Self::join(args),
+            INT => return Self::int(args),
             _ => {}
         }
         Ok(Value::None)
     }
 
-    fn print(args: &Vec<Value>) {
+    fn print(args: &[Value]) {
         for value in args {
             println!("{value}")
         }
     }
 
-    fn get_list_length(args: &Vec<Value>) -> Result<Value, EvalError> {
+    /// Prints the values one after the other, without line breaks
+    fn print_inline(out: &mut impl Write, args: &[Value]) -> Result<Value, EvalError> {
+        for value in args {
+            write!(out, "{value}").map_err(|e| EvalError::IoError(e.to_string()))?;
+        }
+        out.flush().map_err(|e| EvalError::IoError(e.to_string()))?;
+        Ok(Value::None)
+    }
+
+    /// Reads a line, after printing the optional prompt.
+    /// Returns `none` when there is nothing left to read.
+    fn input(reader: &mut impl BufRead, out: &mut impl Write, args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [] => {}
+            [Value::StringValue(prompt)] => {
+                write!(out, "{prompt}").and_then(|_| out.flush()).map_err(|e| EvalError::IoError(e.to_string()))?;
+            }
+            _ => return Err(EvalError::Error("The function `input` can only be used with an optional prompt of type `string`"))
+        }
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => Ok(Value::None),
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Ok(Value::StringValue(line))
+            }
+            Err(e) => Err(EvalError::IoError(e.to_string())),
+        }
+    }
+
+    /// Fails when the condition is false, with the optional message.
+    /// The condition follows the same rules as the condition of an `if`.
+    fn assert(args: &[Value]) -> Result<Value, EvalError> {
+        let (condition, message) = match args {
+            [condition] => (condition, "assertion failed"),
+            [condition, Value::StringValue(message)] => (condition, message.as_str()),
+            _ => return Err(EvalError::Error("The function `assert` must be used with a condition and an optional message of type `string`"))
+        };
+        if condition.as_bool()? {
+            Ok(Value::None)
+        } else {
+            Err(EvalError::AssertionFailed(message.to_string()))
+        }
+    }
+
+    /// Stops the evaluation with the message
+    fn error(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(message)] => Err(EvalError::UserError(message.clone())),
+            _ => Err(EvalError::Error("The function `error` can only be used with a single message of type `string`"))
+        }
+    }
+
+    fn get_list_length(args: &[Value]) -> Result<Value, EvalError> {
         if args.len() != 1 {
             Err(EvalError::Error("The function `len` can only be used with a single argument"))
         } else {
@@ -42,4 +174,632 @@ impl Std {
         }
     }
 
+    fn truthy(args: &[Value]) -> Result<Value, EvalError> {
+        if args.len() != 1 {
+            Err(EvalError::Error("The function `truthy` can only be used with a single argument"))
+        } else {
+            Ok(Value::BoolValue(args[0].is_truthy()))
+        }
+    }
+
+    /// Reads the arguments `(list, n)` of `chunks` and `windows`
+    fn get_list_and_size(args: &[Value]) -> Result<(&Vec<Value>, usize), EvalError> {
+        match args {
+            [Value::List(values), Value::IntValue(n)] if *n > 0 => Ok((values, *n as usize)),
+            [Value::List(_), Value::IntValue(_)] => Err(EvalError::Error("The size must be strictly positive")),
+            _ => Err(EvalError::Error("Expected a `list` and an `int` as arguments"))
+        }
+    }
+
+    fn chunks(args: &[Value]) -> Result<Value, EvalError> {
+        let (values, n) = Self::get_list_and_size(args)?;
+        Ok(Value::List(Rc::new(values.chunks(n).map(|chunk| Value::List(Rc::new(chunk.to_vec()))).collect())))
+    }
+
+    fn windows(args: &[Value]) -> Result<Value, EvalError> {
+        let (values, n) = Self::get_list_and_size(args)?;
+        Ok(Value::List(Rc::new(values.windows(n).map(|window| Value::List(Rc::new(window.to_vec()))).collect())))
+    }
+
+    fn to_json(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [value] => Ok(Value::StringValue(value.to_json())),
+            _ => Err(EvalError::Error("The function `to_json` can only be used with a single argument"))
+        }
+    }
+
+    fn from_json(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(text)] => Value::from_json(text),
+            _ => Err(EvalError::Error("The function `from_json` can only be used with a single argument of type `string`"))
+        }
+    }
+
+    /// Calls the function on each element of the list
+    fn map(args: &[Value], module: Option<&Module>) -> Result<Value, EvalError> {
+        match args {
+            [function @ (Value::Function(_) | Value::Closure(..)), Value::List(values)] => {
+                let mut to_return = vec![];
+                for value in values.iter() {
+                    to_return.push(function.clone().call(vec![value.clone()], module)?);
+                }
+                Ok(Value::List(Rc::new(to_return)))
+            }
+            _ => Err(EvalError::Error("The function `map` must be used with a function and a `list`"))
+        }
+    }
+
+    /// Threads an accumulator through the list, starting from `init`: the function is called
+    /// with the accumulator and each element, and returns the next accumulator
+    fn reduce(args: &[Value], module: Option<&Module>) -> Result<Value, EvalError> {
+        match args {
+            [function @ (Value::Function(_) | Value::Closure(..)), Value::List(values), init] => {
+                let mut accumulator = init.clone();
+                for value in values.iter() {
+                    accumulator = function.clone().call(vec![accumulator, value.clone()], module)?;
+                }
+                Ok(accumulator)
+            }
+            _ => Err(EvalError::Error("The function `reduce` must be used with a function, a `list` and an initial value"))
+        }
+    }
+
+    /// Keeps the elements of the list for which the function returns true.
+    /// The results follow the same rules as the condition of an `if`.
+    fn filter(args: &[Value], module: Option<&Module>) -> Result<Value, EvalError> {
+        match args {
+            [predicate @ (Value::Function(_) | Value::Closure(..)), Value::List(values)] => {
+                let mut to_return = vec![];
+                for value in values.iter() {
+                    if predicate.clone().call(vec![value.clone()], module)?.as_bool()? {
+                        to_return.push(value.clone());
+                    }
+                }
+                Ok(Value::List(Rc::new(to_return)))
+            }
+            _ => Err(EvalError::Error("The function `filter` must be used with a function and a `list`"))
+        }
+    }
+
+    fn read_file(fs: &dyn FileSystem, args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(path)] => fs.read_to_string(path)
+                .map(Value::StringValue)
+                .map_err(|e| EvalError::IoError(e.to_string())),
+            _ => Err(EvalError::Error("The function `read_file` can only be used with a single argument of type `string`"))
+        }
+    }
+
+    /// Writes the string to the file, and returns the number of bytes written
+    fn write_file(fs: &dyn FileSystem, args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(path), Value::StringValue(contents)] => fs.write(path, contents)
+                .map(|n| Value::IntValue(n as i64))
+                .map_err(|e| EvalError::IoError(e.to_string())),
+            _ => Err(EvalError::Error("The function `write_file` must be used with a path and a `string`"))
+        }
+    }
+
+    /// Splits a string on `\n` and `\r\n`.
+    /// A trailing line break does not produce a trailing empty line.
+    fn split_lines(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(text)] => Ok(Value::List(Rc::new(text.lines().map(|line| Value::StringValue(line.to_string())).collect()))),
+            _ => Err(EvalError::Error("The function `split_lines` can only be used with a single argument of type `string`"))
+        }
+    }
+
+    /// Splits a string on each occurrence of the separator
+    fn split(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::StringValue(_), Value::StringValue(separator)] if separator.is_empty() => {
+                Err(EvalError::Error("The separator of `split` can't be empty"))
+            }
+            [Value::StringValue(text), Value::StringValue(separator)] => {
+                Ok(Value::List(Rc::new(text.split(separator.as_str()).map(|part| Value::StringValue(part.to_string())).collect())))
+            }
+            _ => Err(EvalError::Error("The function `split` must be used with a `string` and a separator of type `string`"))
+        }
+    }
+
+    /// Concatenates the strings of the list, with the separator between them
+    fn join(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::List(values), Value::StringValue(separator)] => {
+                let mut parts = vec![];
+                for value in values.iter() {
+                    match value {
+                        Value::StringValue(part) => parts.push(part.as_str()),
+                        _ => return Err(EvalError::Error("The function `join` can only be used with a list of `string`"))
+                    }
+                }
+                Ok(Value::StringValue(parts.join(separator)))
+            }
+            _ => Err(EvalError::Error("The function `join` must be used with a `list` and a separator of type `string`"))
+        }
+    }
+
+    /// Returns the name of the type of the value: `int`, `bool`, `string`, `list`, `map`, `function` or `none`
+    fn type_of(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [value] => Ok(Value::StringValue(value.type_name().to_string())),
+            _ => Err(EvalError::Error("The function `type` can only be used with a single argument"))
+        }
+    }
+
+    /// Converts any value to its printed representation
+    fn str(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [value] => Ok(Value::StringValue(value.to_string())),
+            _ => Err(EvalError::Error("The function `str` can only be used with a single argument"))
+        }
+    }
+
+    /// Parses a decimal integer, surrounding whitespace is ignored.
+    /// Integers are returned as they are.
+    fn int(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::IntValue(i)] => Ok(Value::IntValue(*i)),
+            [Value::StringValue(text)] => text.trim().parse()
+                .map(Value::IntValue)
+                .map_err(|_| EvalError::Error("The string given to `int` is not a valid integer")),
+            _ => Err(EvalError::Error("The function `int` can only be used with a single argument of type `string` or `int`"))
+        }
+    }
+
+    /// Returns the smallest (`Less`) or the largest (`Greater`) integer, among two arguments
+    /// or among the elements of a single list argument
+    fn extremum(args: &[Value], wanted: Ordering) -> Result<Value, EvalError> {
+        let values = match args {
+            [Value::List(values)] => values.as_slice(),
+            [_, _] => args,
+            _ => return Err(EvalError::Error("The functions `min` and `max` must be used with two arguments or a `list`"))
+        };
+        let mut values = values.iter();
+        let mut extremum = values.next().ok_or(EvalError::Error("The functions `min` and `max` can't be used with an empty list"))?;
+        // Values of different types can't be compared, see `Value::compare`
+        for value in values {
+            if value.compare(extremum)? == wanted {
+                extremum = value;
+            }
+        }
+        // All the values have the same type, otherwise they could not be compared
+        match extremum {
+            Value::IntValue(_) => Ok(extremum.clone()),
+            _ => Err(EvalError::Error("The functions `min` and `max` can only be used with values of type `int`"))
+        }
+    }
+
+    fn abs(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::IntValue(i)] => i.checked_abs().map(Value::IntValue).ok_or(EvalError::IntegerOverflow),
+            _ => Err(EvalError::Error("The function `abs` can only be used with a single argument of type `int`"))
+        }
+    }
+
+    fn keys(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::Map(entries)] => Ok(Value::List(Rc::new(entries.iter().map(|(key, _)| key.clone()).collect()))),
+            _ => Err(EvalError::Error("The function `keys` can only be used with a single argument of type `map`"))
+        }
+    }
+
+    fn values(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::Map(entries)] => Ok(Value::List(Rc::new(entries.iter().map(|(_, value)| value.clone()).collect()))),
+            _ => Err(EvalError::Error("The function `values` can only be used with a single argument of type `map`"))
+        }
+    }
+
+    /// Returns whether the item is an element of the list, or a key of the map
+    fn contains(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::List(values), item] => Ok(Value::BoolValue(values.contains(item))),
+            [Value::Map(entries), item] => Ok(Value::BoolValue(entries.iter().any(|(key, _)| key == item))),
+            _ => Err(EvalError::Error("The function `contains` must be used with a `list` or a `map`, and an item"))
+        }
+    }
+
+    fn reverse(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::List(values)] => Ok(Value::List(Rc::new(values.iter().rev().cloned().collect()))),
+            _ => Err(EvalError::Error("The function `reverse` can only be used with a single argument of type `list`"))
+        }
+    }
+
+    /// Returns a sorted copy of the list.
+    /// All the elements must be comparable with each other.
+    fn sort(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::List(values)] => {
+                // The sort fails with the error of the first two values that can't be compared
+                let mut error = None;
+                let mut sorted = values.clone();
+                Rc::make_mut(&mut sorted).sort_by(|a, b| a.compare(b).unwrap_or_else(|e| {
+                    error.get_or_insert(e);
+                    Ordering::Equal
+                }));
+                match error {
+                    Some(error) => Err(error),
+                    None => Ok(Value::List(sorted))
+                }
+            }
+            _ => Err(EvalError::Error("The function `sort` can only be used with a single argument of type `list`"))
+        }
+    }
+
+    /// Renders an integer with its digits grouped by thousands: `1_000_000`
+    fn group_digits(args: &[Value]) -> Result<Value, EvalError> {
+        match args {
+            [Value::IntValue(i)] => {
+                let digits = i.unsigned_abs().to_string();
+                let mut grouped = String::new();
+                if *i < 0 {
+                    grouped.push('-');
+                }
+                for (position, digit) in digits.chars().enumerate() {
+                    if position > 0 && (digits.len() - position) % 3 == 0 {
+                        grouped.push('_');
+                    }
+                    grouped.push(digit);
+                }
+                Ok(Value::StringValue(grouped))
+            }
+            _ => Err(EvalError::Error("The function `group_digits` can only be used with a single argument of type `int`"))
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io;
+    use std::rc::Rc;
+
+    use crate::ast::expression::Value;
+    use crate::ast::expression::Value::{BoolValue, IntValue, List};
+    use crate::ast::statement::StatementEval;
+    use crate::environment::Environment;
+    use crate::error::EvalError;
+    use crate::filesystem::FileSystem;
+    use crate::parser::{parse_expression, Parser};
+    use crate::std::Std;
+    use crate::token::tokenize;
+
+    fn eval(text: &str) -> Result<Value, EvalError> {
+        let tokens = tokenize(text).unwrap();
+        let ast = parse_expression(&tokens).unwrap();
+        ast.eval(&mut Environment::new(), None)
+    }
+
+    #[test]
+    fn test_truthy() {
+        assert_eq!(eval("truthy(0)"), Ok(BoolValue(false)));
+        assert_eq!(eval("truthy(3)"), Ok(BoolValue(true)));
+        assert_eq!(eval("truthy([])"), Ok(BoolValue(false)));
+        assert_eq!(eval("truthy([0])"), Ok(BoolValue(true)));
+        assert_eq!(eval("truthy(\"x\")"), Ok(BoolValue(true)));
+        assert_eq!(eval("truthy(\"\")"), Ok(BoolValue(false)));
+        assert!(eval("truthy(1, 2)").is_err());
+    }
+
+    fn list(values: &[i64]) -> Value {
+        List(Rc::new(values.iter().map(|v| IntValue(*v)).collect()))
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(eval("chunks([1,2,3,4,5], 2)"), Ok(List(Rc::new(vec![list(&[1, 2]), list(&[3, 4]), list(&[5])]))));
+        assert_eq!(eval("chunks([1,2,3], 3)"), Ok(List(Rc::new(vec![list(&[1, 2, 3])]))));
+        assert_eq!(eval("chunks([], 2)"), Ok(List(Rc::new(vec![]))));
+        assert!(eval("chunks([1,2,3], 0)").is_err());
+        assert!(eval("chunks(1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        assert_eq!(
+            eval("from_json(to_json([1, \"a\", true]))"),
+            Ok(List(Rc::new(vec![IntValue(1), Value::StringValue("a".to_string()), BoolValue(true)])))
+        );
+        assert_eq!(eval("to_json({\"a\": [none]})"), Ok(Value::StringValue("{\"a\":[null]}".to_string())));
+        assert!(matches!(eval("from_json(\"[1,\")"), Err(EvalError::Error(_))));
+        assert!(eval("from_json(1)").is_err());
+    }
+
+    fn run(text: &str) -> Result<StatementEval, EvalError> {
+        let tokens = tokenize(text).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let module = parser.parse_module().unwrap();
+        module.run()
+    }
+
+    #[test]
+    fn test_map() {
+        let text = "\
+fn double(x) {
+    return 2 * x;
+}
+
+fn main() {
+    return map(double, [1, 2, 3]);
+}";
+        assert_eq!(run(text), Ok(StatementEval::Return(list(&[2, 4, 6]))));
+
+        assert_eq!(eval("map(len, [[1], [], [1, 2]])"), Ok(list(&[1, 0, 2])));
+        assert_eq!(eval("map(len, [])"), Ok(list(&[])));
+        // Errors of the inner calls are propagated
+        assert!(eval("map(len, [1])").is_err());
+        assert!(eval("map(1, [1])").is_err());
+    }
+
+    #[test]
+    fn test_reduce() {
+        let text = "\
+fn add(a, b) {
+    return a + b;
+}
+
+fn main() {
+    return reduce(add, [1, 2, 3, 4], 0);
+}";
+        assert_eq!(run(text), Ok(StatementEval::Return(IntValue(10))));
+
+        assert_eq!(eval("reduce(fn(acc, x) { return acc + [x * x]; }, [1, 2, 3], [])"), Ok(list(&[1, 4, 9])));
+        assert_eq!(eval("reduce(max, [], 5)"), Ok(IntValue(5)));
+        assert_eq!(eval("reduce(max, [3, 7, 2], 0)"), Ok(IntValue(7)));
+        // Errors of the inner calls are propagated
+        assert!(matches!(eval("reduce(max, [1, \"a\"], 0)"), Err(EvalError::TypeError(_))));
+        assert!(eval("reduce(max, [1])").is_err());
+        assert!(eval("reduce(1, [1], 0)").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let text = "\
+fn is_even(x) {
+    return x - 2 * (x / 2) == 0;
+}
+
+fn main() {
+    return filter(is_even, [1, 2, 3, 4]);
+}";
+        assert_eq!(run(text), Ok(StatementEval::Return(list(&[2, 4]))));
+
+        assert_eq!(eval("filter(fn(x) { return x > 2; }, [3, 1, 4])"), Ok(list(&[3, 4])));
+        // Integers are conditions, as in `if`
+        assert_eq!(eval("filter(abs, [0, 1, 0 - 2])"), Ok(list(&[1, -2])));
+        assert_eq!(eval("filter(len, [])"), Ok(list(&[])));
+        assert!(matches!(eval("filter(str, [1])"), Err(EvalError::Error(_))));
+        assert!(eval("filter(len, [1])").is_err());
+        assert!(eval("filter([1], len)").is_err());
+    }
+
+    /// Files kept in memory
+    #[derive(Default)]
+    struct MemoryFileSystem {
+        files: RefCell<HashMap<String, String>>,
+    }
+
+    impl FileSystem for MemoryFileSystem {
+        fn read_to_string(&self, path: &str) -> io::Result<String> {
+            self.files.borrow().get(path).cloned().ok_or(io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn write(&self, path: &str, contents: &str) -> io::Result<usize> {
+            self.files.borrow_mut().insert(path.to_string(), contents.to_string());
+            Ok(contents.len())
+        }
+    }
+
+    fn string(s: &str) -> Value {
+        Value::StringValue(s.to_string())
+    }
+
+    #[test]
+    fn test_read_and_write_file() {
+        let fs = MemoryFileSystem::default();
+        assert_eq!(Std::write_file(&fs, &[string("a.txt"), string("héllo")]), Ok(IntValue(6)));
+        assert_eq!(Std::read_file(&fs, &[string("a.txt")]), Ok(string("héllo")));
+        assert!(matches!(Std::read_file(&fs, &[string("b.txt")]), Err(EvalError::IoError(_))));
+        assert!(Std::read_file(&fs, &[IntValue(1)]).is_err());
+        assert!(Std::write_file(&fs, &[string("a.txt")]).is_err());
+    }
+
+    #[test]
+    fn test_read_and_write_temp_file() {
+        let path = std::env::temp_dir().join(format!("interpreter_test_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(eval(&format!("write_file(\"{path}\", \"some text\")")), Ok(IntValue(9)));
+        assert_eq!(eval(&format!("read_file(\"{path}\")")), Ok(string("some text")));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let lines = |values: &[&str]| Ok(List(Rc::new(values.iter().map(|s| string(s)).collect())));
+        assert_eq!(eval("split_lines(\"a\nb\nc\")"), lines(&["a", "b", "c"]));
+        assert_eq!(eval("split_lines(\"a\r\nb\n\")"), lines(&["a", "b"]));
+        assert_eq!(eval("split_lines(\"a\n\nb\")"), lines(&["a", "", "b"]));
+        assert_eq!(eval("split_lines(\"\")"), lines(&[]));
+        assert!(eval("split_lines(1)").is_err());
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let strings = |values: &[&str]| Ok(List(Rc::new(values.iter().map(|s| string(s)).collect())));
+        assert_eq!(eval("split(\"a,b,c\", \",\")"), strings(&["a", "b", "c"]));
+        assert_eq!(eval("split(\"a, b\", \", \")"), strings(&["a", "b"]));
+        assert_eq!(eval("split(\"a,,b,\", \",\")"), strings(&["a", "", "b", ""]));
+        assert_eq!(eval("split(\"abc\", \",\")"), strings(&["abc"]));
+        assert!(eval("split(\"abc\", \"\")").is_err());
+        assert!(eval("split(1, \",\")").is_err());
+
+        assert_eq!(eval("join([\"a\", \"b\", \"c\"], \",\")"), Ok(string("a,b,c")));
+        assert_eq!(eval("join(split(\"a,b,c\", \",\"), \",\") == \"a,b,c\""), Ok(BoolValue(true)));
+        assert_eq!(eval("join([], \",\")"), Ok(string("")));
+        assert!(eval("join([1, 2], \",\")").is_err());
+        assert!(eval("join([\"a\"], 1)").is_err());
+    }
+
+    #[test]
+    fn test_str() {
+        assert_eq!(eval("str(42) == \"42\""), Ok(BoolValue(true)));
+        assert_eq!(eval("str(true) == \"true\""), Ok(BoolValue(true)));
+        assert_eq!(eval("str(0 - 7)"), Ok(string("-7")));
+        assert_eq!(eval("str(\"a\")"), Ok(string("a")));
+        assert_eq!(eval("\"n = \" + str(3)"), Ok(string("n = 3")));
+        assert_eq!(eval("str([1, \"a\", [2], []])"), Ok(string("[1, a, [2], []]")));
+        assert_eq!(eval("str({1: [true], \"k\": {}})"), Ok(string("{1: [true], k: {}}")));
+        assert!(eval("str(1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_int() {
+        assert_eq!(eval("int(\"42\")"), Ok(IntValue(42)));
+        assert_eq!(eval("int(\" -7\n\")"), Ok(IntValue(-7)));
+        assert_eq!(eval("int(3)"), Ok(IntValue(3)));
+        assert_eq!(eval("int(str(123)) + 1"), Ok(IntValue(124)));
+        assert!(matches!(eval("int(\"x\")"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("int(\"\")"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("int(\"99999999999999999999\")"), Err(EvalError::Error(_))));
+        assert!(eval("int(true)").is_err());
+    }
+
+    #[test]
+    fn test_type() {
+        assert_eq!(eval("type(1)"), Ok(string("int")));
+        assert_eq!(eval("type(true)"), Ok(string("bool")));
+        assert_eq!(eval("type(\"a\")"), Ok(string("string")));
+        assert_eq!(eval("type([1])"), Ok(string("list")));
+        assert_eq!(eval("type({1: 2})"), Ok(string("map")));
+        assert_eq!(eval("type(len)"), Ok(string("function")));
+        assert_eq!(eval("type(fn(x) { return x; })"), Ok(string("function")));
+        assert_eq!(eval("type(none)"), Ok(string("none")));
+        assert_eq!(eval("type(type(1))"), Ok(string("string")));
+        assert!(eval("type()").is_err());
+    }
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(eval("group_digits(0)"), Ok(string("0")));
+        assert_eq!(eval("group_digits(999)"), Ok(string("999")));
+        assert_eq!(eval("group_digits(1000)"), Ok(string("1_000")));
+        assert_eq!(eval("group_digits(1000000)"), Ok(string("1_000_000")));
+        assert_eq!(eval("group_digits(12345678)"), Ok(string("12_345_678")));
+        assert_eq!(eval("group_digits(0 - 1234)"), Ok(string("-1_234")));
+        assert_eq!(eval("group_digits(0 - 123)"), Ok(string("-123")));
+        assert_eq!(eval("group_digits(0x7FFFFFFFFFFFFFFF)"), Ok(string("9_223_372_036_854_775_807")));
+        assert!(eval("group_digits(\"1000\")").is_err());
+    }
+
+    #[test]
+    fn test_print_inline() {
+        let mut out = vec![];
+        assert_eq!(Std::print_inline(&mut out, &[string("a"), IntValue(1)]), Ok(Value::None));
+        assert_eq!(Std::print_inline(&mut out, &[list(&[2])]), Ok(Value::None));
+        assert_eq!(String::from_utf8(out).unwrap(), "a1[2]");
+
+        assert_eq!(eval("print_inline(\"\")"), Ok(Value::None));
+    }
+
+    #[test]
+    fn test_input() {
+        let mut reader = "first\r\nsecond\nlast".as_bytes();
+        let mut out = vec![];
+        assert_eq!(Std::input(&mut reader, &mut out, &[]), Ok(string("first")));
+        assert_eq!(Std::input(&mut reader, &mut out, &[string("> ")]), Ok(string("second")));
+        assert_eq!(Std::input(&mut reader, &mut out, &[]), Ok(string("last")));
+        assert_eq!(Std::input(&mut reader, &mut out, &[]), Ok(Value::None));
+        assert_eq!(String::from_utf8(out).unwrap(), "> ");
+        assert!(Std::input(&mut reader, &mut vec![], &[IntValue(1)]).is_err());
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(eval("max(1, 2)"), Ok(IntValue(2)));
+        assert_eq!(eval("min(4, 2) == 2"), Ok(BoolValue(true)));
+        assert_eq!(eval("max([1,5,2]) == 5"), Ok(BoolValue(true)));
+        assert_eq!(eval("min([3, 0 - 1, 2])"), Ok(IntValue(-1)));
+        assert_eq!(eval("max([7])"), Ok(IntValue(7)));
+        assert_eq!(eval("max(1, \"a\")"), Err(EvalError::TypeError("`string` and `int` can't be compared".to_string())));
+        assert!(matches!(eval("min([1, \"a\"])"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("max(none, none)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("max(\"b\", \"a\")"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("min([1, 2], [1])"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("max([])"), Err(EvalError::Error(_))));
+        assert!(eval("max(1)").is_err());
+        assert!(eval("max(1, 2, 3)").is_err());
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(eval("abs(0 - 3) == 3"), Ok(BoolValue(true)));
+        assert_eq!(eval("abs(3)"), Ok(IntValue(3)));
+        assert_eq!(eval("abs(0)"), Ok(IntValue(0)));
+        assert_eq!(eval("abs((0 - 0x7FFFFFFFFFFFFFFF) - 1)"), Err(EvalError::IntegerOverflow));
+        assert!(eval("abs(\"a\")").is_err());
+    }
+
+    #[test]
+    fn test_assert() {
+        assert_eq!(eval("assert(true)"), Ok(Value::None));
+        assert_eq!(eval("assert(1 + 1 == 2, \"math\")"), Ok(Value::None));
+        assert_eq!(eval("assert(3)"), Ok(Value::None));
+        assert_eq!(eval("assert(false)"), Err(EvalError::AssertionFailed("assertion failed".to_string())));
+        assert_eq!(eval("assert(0, \"zero\")"), Err(EvalError::AssertionFailed("zero".to_string())));
+        assert!(matches!(eval("assert(\"x\")"), Err(EvalError::Error(_))));
+        assert!(eval("assert(true, 1)").is_err());
+        assert!(eval("assert()").is_err());
+    }
+
+    #[test]
+    fn test_error() {
+        assert_eq!(eval("error(\"boom\")"), Err(EvalError::UserError("boom".to_string())));
+        assert!(matches!(eval("error()"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("error(1)"), Err(EvalError::Error(_))));
+        assert!(matches!(eval("error(\"a\", \"b\")"), Err(EvalError::Error(_))));
+    }
+
+    #[test]
+    fn test_keys_values() {
+        assert_eq!(eval("keys({1: 2, 3: 4})"), Ok(list(&[1, 3])));
+        assert_eq!(eval("values({1: 2, 3: 4})"), Ok(list(&[2, 4])));
+        assert_eq!(eval("keys({})"), Ok(list(&[])));
+        assert!(eval("keys([1])").is_err());
+        assert!(eval("values({1: 2}, 1)").is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        assert_eq!(eval("contains([1,2,3], 2)"), Ok(BoolValue(true)));
+        assert_eq!(eval("contains([1,2], 5)"), Ok(BoolValue(false)));
+        assert_eq!(eval("contains([], none)"), Ok(BoolValue(false)));
+        assert_eq!(eval("contains({\"a\": 1}, \"a\")"), Ok(BoolValue(true)));
+        assert_eq!(eval("contains({\"a\": 1}, 1)"), Ok(BoolValue(false)));
+        assert!(eval("contains(\"abc\", \"a\")").is_err());
+        assert!(eval("contains([1])").is_err());
+    }
+
+    #[test]
+    fn test_reverse_and_sort() {
+        assert_eq!(eval("reverse([1,2,3]) == [3,2,1]"), Ok(BoolValue(true)));
+        assert_eq!(eval("reverse([])"), Ok(list(&[])));
+        assert_eq!(eval("sort([3,1,2]) == [1,2,3]"), Ok(BoolValue(true)));
+        assert_eq!(eval("sort([\"b\", \"c\", \"a\"])"), Ok(List(Rc::new(vec![string("a"), string("b"), string("c")]))));
+        assert_eq!(eval("sort([[2], [1, 2], [1]])"), Ok(List(Rc::new(vec![list(&[1]), list(&[1, 2]), list(&[2])]))));
+        assert!(matches!(eval("sort([1, \"a\"])"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("sort([[1], [1, \"a\"], [1, 2]])"), Err(EvalError::TypeError(_))));
+        assert!(eval("sort(1)").is_err());
+        assert!(eval("reverse(\"abc\")").is_err());
+    }
+
+    #[test]
+    fn test_windows() {
+        assert_eq!(eval("windows([1,2,3], 2)"), Ok(List(Rc::new(vec![list(&[1, 2]), list(&[2, 3])]))));
+        assert_eq!(eval("windows([1,2,3], 4)"), Ok(List(Rc::new(vec![]))));
+        assert!(eval("windows([1,2,3], 0 - 1)").is_err());
+    }
 }
@@ -0,0 +1,174 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned identifier, such as the name of a variable or of a function.
+///
+/// Each name is stored once in a global table, and a symbol is its index in the table:
+/// copying, comparing and hashing a symbol does not read the characters of the name.
+/// The table is shared by all the threads, so that a module parsed on a thread can be
+/// evaluated on another one. Each thread keeps a copy of the names, to read them without
+/// locking the table.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+thread_local! {
+    /// The names of the table known by the thread, by symbol
+    static NAMES: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+}
+
+impl Symbol {
+    /// Returns the symbol of the name, which is added to the table the first time it is seen.
+    /// The names are never removed from the table.
+    pub fn intern(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap();
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// Returns the symbol of a name that is already in the table, without adding it
+    pub fn lookup(name: &str) -> Option<Self> {
+        interner().lock().unwrap().symbols.get(name).copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        let index = self.0 as usize;
+        NAMES.with_borrow_mut(|names| {
+            if index >= names.len() {
+                // The symbol was interned after the last copy of the table
+                names.extend_from_slice(&interner().lock().unwrap().names[names.len()..]);
+            }
+            names[index]
+        })
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(symbol: &Symbol) -> Self {
+        *symbol
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Symbols are ordered by their names, not by the order in which they were interned
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.as_str().cmp(other.as_str())
+        }
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol::Symbol;
+
+    #[test]
+    fn test_intern() {
+        let a = Symbol::intern("symbol_a");
+        assert_eq!(a, Symbol::intern("symbol_a"));
+        assert_eq!(a, Symbol::from("symbol_a".to_string()));
+        assert_ne!(a, Symbol::intern("symbol_b"));
+        assert_eq!(a.as_str(), "symbol_a");
+        assert_eq!(a, "symbol_a");
+        assert_eq!(format!("{a} {a:?}"), "symbol_a \"symbol_a\"");
+        // The same symbols are used by all the threads
+        assert_eq!(std::thread::spawn(|| Symbol::intern("symbol_a")).join().unwrap(), a);
+        let b = std::thread::spawn(|| Symbol::intern("symbol_from_thread")).join().unwrap();
+        assert_eq!(b.as_str(), "symbol_from_thread");
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(Symbol::lookup("lookup_a"), None);
+        let a = Symbol::intern("lookup_a");
+        assert_eq!(Symbol::lookup("lookup_a"), Some(a));
+    }
+
+    #[test]
+    fn test_symbols_are_ordered_by_name() {
+        let z = Symbol::intern("order_z");
+        let a = Symbol::intern("order_a");
+        assert!(a < z);
+        let mut symbols = vec![z, a, Symbol::intern("order_m")];
+        symbols.sort();
+        assert_eq!(symbols, vec!["order_a", "order_m", "order_z"]);
+    }
+}
@@ -1,17 +1,21 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
 use crate::error::TokenError;
-use crate::error::TokenError::UnknownChar;
-use crate::token::Op::{Div, Minus, Plus, Times};
-use crate::token::Token::{Break, Comma, Else, Equal, False, Fn, Ident, If, Integer, LBrace, LBracket, Loop, LPar, RBrace, RBracket, Return, RPar, SemiColon, TokenComp, TokenOp, True};
+use crate::error::TokenError::{IntegerLiteralOverflow, InvalidCharLiteral, InvalidDigit, MissingDigits, UnknownChar};
+use crate::token::Op::{Div, Minus, Plus, Pow, Times};
+use crate::token::Token::{AssertEq, Break, Colon, Comma, Const, Do, Dot, Else, Equal, False, FatArrow, Fn, Ident, If, Integer, LBrace, LBracket, Loop, LPar, Match, RBrace, RBracket, Repeat, Return, RPar, SemiColon, Struct, TokenComp, TokenLogic, TokenOp, True, While};
 
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
 pub enum Op {
     Plus,
     Minus,
     Times,
     Div,
+    Pow,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
 pub enum Comp {
     Equal,
     Lower,
@@ -20,20 +24,34 @@ pub enum Comp {
     HigherEq
 }
 
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum Logic {
+    And,
+    Or
+}
+
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Token {
     TokenOp(Op),
     TokenComp(Comp),
+    TokenLogic(Logic),
     Ident(String),
     Integer(i64),
     String(String),
+    /// Character literal: `'a'`
+    Char(char),
     Equal,
     /// Symbols
     LPar, RPar,
     LBrace, RBrace,
     LBracket, RBracket,
     SemiColon,
+    Colon,
     Comma,
+    /// `.`, before the name of a field
+    Dot,
+    /// `=>`, between the pattern and the body of a match arm
+    FatArrow,
     /// Keywords
     Return,
     Fn,
@@ -41,149 +59,297 @@ pub enum Token {
     Else,
     True,
     False,
+    None,
     Loop,
-    Break
+    Break,
+    Repeat,
+    AssertEq,
+    Const,
+    While,
+    Do,
+    Match,
+    Struct
+}
+
+/// Tokenizes the whole input
+pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenError> {
+    TokenStream::new(input).collect()
 }
 
-pub fn tokenize(input: &String) -> Result<Vec<Token>, TokenError> {
-    let mut tokens = vec![];
+/// A tokenizer that reads the tokens one at a time, when they are requested.
+/// The stream ends after the first error.
+pub struct TokenStream<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// The current character, which has not been tokenized yet
+    ch: Option<char>,
+}
 
-    let mut chars = input.chars().peekable();
-    let mut ch = chars.next();
+impl<'a> TokenStream<'a> {
+    pub fn new(input: &'a str) -> Self {
+        let mut chars = input.chars().peekable();
+        let ch = chars.next();
+        Self { chars, ch }
+    }
 
-    loop {
-        if ch.is_none() {
-            break;
-        }
+    /// Reads the next token, skipping spaces and comments
+    fn next_token(&mut self) -> Option<Result<Token, TokenError>> {
+        let chars = &mut self.chars;
+        loop {
+            let current = self.ch?;
 
-        // Parse a number
-        if let Some(mut num) = ch.unwrap().to_digit(10) {
-            // if char is a digit, accumulate it
-            ch = chars.next();
-            while let Some(next_ch) = ch {
-                if let Some(next_num) = next_ch.to_digit(10) {
-                    num = 10 * num + next_num;
-                    ch = chars.next();
-                } else {
-                    break;
+            // Parse a number written with a base prefix: `0x`, `0b` or `0o`
+            if current == '0' {
+                let radix = match chars.peek() {
+                    Some('x') => Some(16),
+                    Some('b') => Some(2),
+                    Some('o') => Some(8),
+                    _ => None
+                };
+                if let Some(radix) = radix {
+                    chars.next();
+                    let mut num: i64 = 0;
+                    let mut has_digits = false;
+                    self.ch = chars.next();
+                    while let Some(next_ch) = self.ch {
+                        if !next_ch.is_alphanumeric() {
+                            break;
+                        }
+                        match next_ch.to_digit(radix) {
+                            Some(digit) => {
+                                match num.checked_mul(radix as i64).and_then(|num| num.checked_add(digit as i64)) {
+                                    Some(next_num) => num = next_num,
+                                    None => return Some(Err(IntegerLiteralOverflow))
+                                }
+                            }
+                            None => return Some(Err(InvalidDigit(next_ch)))
+                        }
+                        has_digits = true;
+                        self.ch = chars.next();
+                    }
+                    if !has_digits {
+                        return Some(Err(MissingDigits));
+                    }
+                    return Some(Ok(Integer(num)));
                 }
             }
-            tokens.push(Integer(num as i64));
-            continue;
-        }
-        
-        // Parse a word
-        if ch.unwrap().is_alphabetic() || ch.unwrap() == '_' {
-            let mut tmp: String = ch.unwrap().to_string();
-            ch = chars.next();
-            while let Some(next_ch) = ch {
-                if next_ch.is_alphanumeric() || ch.unwrap() == '_' {
-                    tmp.push(next_ch);
-                    ch = chars.next();
-                } else {
-                    break;
+
+            // Parse a number
+            if let Some(digit) = current.to_digit(10) {
+                // if char is a digit, accumulate it
+                let mut num = digit as i64;
+                self.ch = chars.next();
+                while let Some(next_ch) = self.ch {
+                    if let Some(next_num) = next_ch.to_digit(10) {
+                        match num.checked_mul(10).and_then(|num| num.checked_add(next_num as i64)) {
+                            Some(next) => num = next,
+                            None => return Some(Err(IntegerLiteralOverflow))
+                        }
+                        self.ch = chars.next();
+                    } else {
+                        break;
+                    }
                 }
+                return Some(Ok(Integer(num)));
             }
-            tokens.push(match tmp.as_str() {
-                "return" => Return,
-                "fn" => Fn,
-                "if" => If,
-                "else" => Else,
-                "true" => True,
-                "false" => False,
-                "loop" => Loop,
-                "break" => Break,
-                &_ => Ident(tmp)
-            });
-            continue
-        }
-        
-        // Parse a string
-        if ch.unwrap() == '"' {
-            let mut chars_in_string = vec![];
-            while let Some(next_ch) = chars.next() {
-                match next_ch { 
-                    '"' => {
-                        tokens.push(Token::String(chars_in_string.iter().collect()));
-                        break
+
+            // Parse a word
+            if current.is_alphabetic() || current == '_' {
+                let mut tmp: String = current.to_string();
+                self.ch = chars.next();
+                while let Some(next_ch) = self.ch {
+                    if next_ch.is_alphanumeric() || next_ch == '_' {
+                        tmp.push(next_ch);
+                        self.ch = chars.next();
+                    } else {
+                        break;
                     }
-                    _ => chars_in_string.push(next_ch),
                 }
+                return Some(Ok(match tmp.as_str() {
+                    "return" => Return,
+                    "fn" => Fn,
+                    "if" => If,
+                    "else" => Else,
+                    "true" => True,
+                    "false" => False,
+                    "none" => Token::None,
+                    "loop" => Loop,
+                    "break" => Break,
+                    "repeat" => Repeat,
+                    "assert_eq" => AssertEq,
+                    "const" => Const,
+                    "while" => While,
+                    "do" => Do,
+                    "match" => Match,
+                    "struct" => Struct,
+                    &_ => Ident(tmp)
+                }));
             }
-            ch = chars.next();
-            continue
-        }
 
-        // Parse specific character
-        match ch.unwrap() {
-            '+' => tokens.push(TokenOp(Plus)),
-            '-' => tokens.push(TokenOp(Minus)),
-            '/' => {
-                if let Some(&'/') = chars.peek() {
-                    chars.next();
-                    // If `//` is read, then skip until a break
-                    while let Some(char) = chars.next() {
-                        if char == '\n' {
+            // Parse a string
+            if current == '"' {
+                let mut chars_in_string = vec![];
+                let mut token = None;
+                for next_ch in chars.by_ref() {
+                    match next_ch {
+                        '"' => {
+                            token = Some(Token::String(chars_in_string.iter().collect()));
                             break
                         }
+                        _ => chars_in_string.push(next_ch),
                     }
-                } else {
-                    tokens.push(TokenOp(Div))
-                }
-            },
-            '*' => tokens.push(TokenOp(Times)),
-            '(' => tokens.push(LPar),
-            ')' => tokens.push(RPar),
-            '{' => tokens.push(LBrace),
-            '}' => tokens.push(RBrace),
-            '[' => tokens.push(LBracket),
-            ']' => tokens.push(RBracket),
-            '=' => {
-                if let Some(&'=') = chars.peek() {
-                    chars.next();
-                    tokens.push(TokenComp(Comp::Equal))
-                } else {
-                    tokens.push(Equal)
                 }
-            },
-            '<' => {
-                if let Some(&'=') = chars.peek() {
-                    chars.next();
-                    tokens.push(TokenComp(Comp::LowerEq))
-                } else {
-                    tokens.push(TokenComp(Comp::Lower))
+                self.ch = chars.next();
+                match token {
+                    Some(token) => return Some(Ok(token)),
+                    None => continue
                 }
             }
-            '>' => {
-                if let Some(&'=') = chars.peek() {
-                    chars.next();
-                    tokens.push(TokenComp(Comp::HigherEq))
-                } else {
-                    tokens.push(TokenComp(Comp::Higher))
-                }
+
+            // Parse a character literal
+            if current == '\'' {
+                let token = Self::read_char(chars);
+                self.ch = chars.next();
+                return Some(token);
             }
-            ';' => tokens.push(SemiColon),
-            ',' => tokens.push(Comma),
-            ' ' | '\r' | '\t' | '\n' => {}
-            _ => {
-                return Err(UnknownChar(ch.unwrap()))
+
+            // Parse specific character
+            let token = match current {
+                '+' => Some(TokenOp(Plus)),
+                '-' => Some(TokenOp(Minus)),
+                '/' => {
+                    if let Some(&'/') = chars.peek() {
+                        chars.next();
+                        // If `//` is read, then skip until a break
+                        for char in chars.by_ref() {
+                            if char == '\n' {
+                                break
+                            }
+                        }
+                        None
+                    } else {
+                        Some(TokenOp(Div))
+                    }
+                },
+                '*' => {
+                    if let Some(&'*') = chars.peek() {
+                        chars.next();
+                        Some(TokenOp(Pow))
+                    } else {
+                        Some(TokenOp(Times))
+                    }
+                },
+                '(' => Some(LPar),
+                ')' => Some(RPar),
+                '{' => Some(LBrace),
+                '}' => Some(RBrace),
+                '[' => Some(LBracket),
+                ']' => Some(RBracket),
+                '=' => {
+                    match chars.peek() {
+                        Some(&'=') => {
+                            chars.next();
+                            Some(TokenComp(Comp::Equal))
+                        }
+                        Some(&'>') => {
+                            chars.next();
+                            Some(FatArrow)
+                        }
+                        _ => Some(Equal)
+                    }
+                },
+                '<' => {
+                    if let Some(&'=') = chars.peek() {
+                        chars.next();
+                        Some(TokenComp(Comp::LowerEq))
+                    } else {
+                        Some(TokenComp(Comp::Lower))
+                    }
+                }
+                '>' => {
+                    if let Some(&'=') = chars.peek() {
+                        chars.next();
+                        Some(TokenComp(Comp::HigherEq))
+                    } else {
+                        Some(TokenComp(Comp::Higher))
+                    }
+                }
+                '&' => {
+                    if let Some(&'&') = chars.peek() {
+                        chars.next();
+                        Some(TokenLogic(Logic::And))
+                    } else {
+                        return Some(Err(UnknownChar('&')))
+                    }
+                }
+                '|' => {
+                    if let Some(&'|') = chars.peek() {
+                        chars.next();
+                        Some(TokenLogic(Logic::Or))
+                    } else {
+                        return Some(Err(UnknownChar('|')))
+                    }
+                }
+                ';' => Some(SemiColon),
+                ':' => Some(Colon),
+                ',' => Some(Comma),
+                '.' => Some(Dot),
+                ' ' | '\r' | '\t' | '\n' => None,
+                _ => {
+                    return Some(Err(UnknownChar(current)))
+                }
+            };
+
+            self.ch = chars.next();
+            if let Some(token) = token {
+                return Some(Ok(token));
             }
         }
+    }
+}
 
-        ch = chars.next();
+impl TokenStream<'_> {
+    /// Reads a character literal after its opening quote, up to the closing quote.
+    /// The escapes `\n`, `\t`, `\\` and `\'` are supported.
+    fn read_char(chars: &mut Peekable<Chars>) -> Result<Token, TokenError> {
+        let value = match chars.next() {
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('\\') => '\\',
+                Some('\'') => '\'',
+                _ => return Err(InvalidCharLiteral)
+            },
+            Some('\'') | None => return Err(InvalidCharLiteral),
+            Some(value) => value
+        };
+        match chars.next() {
+            Some('\'') => Ok(Token::Char(value)),
+            _ => Err(InvalidCharLiteral)
+        }
     }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Result<Token, TokenError>;
 
-    Ok(tokens)
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.next_token();
+        if let Some(Err(_)) = token {
+            self.ch = None;
+        }
+        token
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::token::{Comp, Token, tokenize};
-    use crate::token::Op::{Div, Minus, Plus, Times};
-    use crate::token::Token::{Equal, Ident, If, Integer, LBrace, LPar, RBrace, Return, RPar, SemiColon, TokenComp, TokenOp};
+    use crate::error::TokenError;
+    use crate::token::{Comp, Logic, Token, tokenize, TokenStream};
+    use crate::token::Op::{Div, Minus, Plus, Pow, Times};
+    use crate::token::Token::{Equal, Ident, If, Integer, LBrace, LPar, RBrace, Repeat, Return, RPar, SemiColon, TokenComp, TokenOp};
 
     fn assert_tokens(text: &str, tokens: Vec<Token>) {
-        let computed = tokenize(&text.to_string()).unwrap();
+        let computed = tokenize(text).unwrap();
         assert_eq!(computed, tokens)
     }
 
@@ -239,6 +405,14 @@ mod tests {
             "if (1) { return 1; }",
             vec![If, LPar, Integer(1), RPar, LBrace, Return, Integer(1), SemiColon, RBrace],
         );
+        assert_tokens(
+            "repeat 3 {}",
+            vec![Repeat, Integer(3), LBrace, RBrace],
+        );
+        assert_tokens(
+            "assert_eq 1, a;",
+            vec![Token::AssertEq, Integer(1), Token::Comma, Ident("a".to_string()), SemiColon],
+        );
     }
     
     #[test]
@@ -257,6 +431,11 @@ mod tests {
             "1 = 2",
             vec![Integer(1), Equal, Integer(2)],
         );
+
+        assert_tokens(
+            "match x { _ => {} }",
+            vec![Token::Match, Ident("x".to_string()), LBrace, Ident("_".to_string()), Token::FatArrow, LBrace, RBrace, RBrace],
+        );
         
         assert_tokens(
             "1 < 2",
@@ -267,6 +446,68 @@ mod tests {
             "1 <= 2",
             vec![Integer(1), TokenComp(Comp::LowerEq), Integer(2)],
         );
+
+        assert_tokens(
+            "a && b || c",
+            vec![Ident("a".to_string()), Token::TokenLogic(Logic::And), Ident("b".to_string()), Token::TokenLogic(Logic::Or), Ident("c".to_string())],
+        );
+        assert!(tokenize("a & b").is_err());
+
+        assert_tokens(
+            "2 ** 3 * 4",
+            vec![Integer(2), TokenOp(Pow), Integer(3), TokenOp(Times), Integer(4)],
+        );
+
+        assert_tokens(
+            "i++; j--;",
+            vec![Ident("i".to_string()), TokenOp(Plus), TokenOp(Plus), SemiColon, Ident("j".to_string()), TokenOp(Minus), TokenOp(Minus), SemiColon],
+        );
+        assert_tokens("1--2", vec![Integer(1), TokenOp(Minus), TokenOp(Minus), Integer(2)]);
+        assert_tokens(
+            "1 - -2 + +3",
+            vec![Integer(1), TokenOp(Minus), TokenOp(Minus), Integer(2), TokenOp(Plus), TokenOp(Plus), Integer(3)],
+        );
+    }
+
+    #[test]
+    fn test_integer_with_base_prefix() {
+        assert_tokens("0xFF", vec![Integer(255)]);
+        assert_tokens("0xff", vec![Integer(255)]);
+        assert_tokens("0b1010", vec![Integer(10)]);
+        assert_tokens("0o17", vec![Integer(15)]);
+        assert_tokens("0x10+0b1", vec![Integer(16), TokenOp(Plus), Integer(1)]);
+        // plain decimal still works, including a leading zero
+        assert_tokens("0", vec![Integer(0)]);
+        assert_tokens("012", vec![Integer(12)]);
+
+        assert!(matches!(tokenize("0b102"), Err(TokenError::InvalidDigit('2'))));
+        assert!(matches!(tokenize("0o8"), Err(TokenError::InvalidDigit('8'))));
+        assert!(matches!(tokenize("0xG"), Err(TokenError::InvalidDigit('G'))));
+        assert!(matches!(tokenize("0x"), Err(TokenError::MissingDigits)));
+        assert!(matches!(tokenize("0x1FFFFFFFFFFFFFFFF"), Err(TokenError::IntegerLiteralOverflow)));
+    }
+
+    #[test]
+    fn test_large_integer() {
+        assert_tokens("9999999999", vec![Integer(9999999999)]);
+        assert_tokens("9223372036854775807", vec![Integer(i64::MAX)]);
+        assert!(matches!(tokenize("9223372036854775808"), Err(TokenError::IntegerLiteralOverflow)));
+    }
+
+    #[test]
+    fn test_map_literal() {
+        assert_tokens(
+            "{\"a\": 1}",
+            vec![LBrace, Token::String("a".to_string()), Token::Colon, Integer(1), RBrace],
+        );
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_tokens(
+            "struct P { x } p.x",
+            vec![Token::Struct, Ident("P".to_string()), LBrace, Ident("x".to_string()), RBrace, Ident("p".to_string()), Token::Dot, Ident("x".to_string())],
+        );
     }
 
     #[test]
@@ -298,4 +539,53 @@ mod tests {
         );
 
     }
+
+    #[test]
+    fn test_identifiers() {
+        assert_tokens("foo_bar_baz _x x_1", vec![Ident("foo_bar_baz".to_string()), Ident("_x".to_string()), Ident("x_1".to_string())]);
+        assert_tokens("été = größe_2;", vec![Ident("été".to_string()), Equal, Ident("größe_2".to_string()), SemiColon]);
+        assert_tokens("λ(数)", vec![Ident("λ".to_string()), LPar, Ident("数".to_string()), RPar]);
+    }
+
+    #[test]
+    fn test_char() {
+        assert_tokens("'a' 'Z'", vec![Token::Char('a'), Token::Char('Z')]);
+        assert_tokens("'\\n' '\\t' '\\\\' '\\''", vec![Token::Char('\n'), Token::Char('\t'), Token::Char('\\'), Token::Char('\'')]);
+        assert_tokens("a = ' ';", vec![Ident("a".to_string()), Equal, Token::Char(' '), SemiColon]);
+        for text in ["''", "'ab'", "'a", "'\\x'"] {
+            assert_eq!(tokenize(text), Err(TokenError::InvalidCharLiteral), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_token_stream() {
+        let stream = |input: &str| TokenStream::new(input).collect::<Result<Vec<_>, _>>();
+        assert_eq!(
+            stream("fn main() { return 1 + 2; }"),
+            Ok(vec![Token::Fn, Ident("main".to_string()), LPar, RPar, LBrace, Return, Integer(1), TokenOp(Plus), Integer(2), SemiColon, RBrace]),
+        );
+        assert_eq!(
+            stream("a = [0x1F, 0b10]; // comment\nb = \"text\" <= c"),
+            Ok(vec![
+                Ident("a".to_string()), Equal, Token::LBracket, Integer(31), Token::Comma, Integer(2), Token::RBracket, SemiColon,
+                Ident("b".to_string()), Equal, Token::String("text".to_string()), TokenComp(Comp::LowerEq), Ident("c".to_string()),
+            ]),
+        );
+        assert_eq!(
+            stream("x ** 2 && y || z"),
+            Ok(vec![
+                Ident("x".to_string()), TokenOp(Pow), Integer(2), Token::TokenLogic(Logic::And), Ident("y".to_string()),
+                Token::TokenLogic(Logic::Or), Ident("z".to_string()),
+            ]),
+        );
+        assert_eq!(stream("0x"), Err(TokenError::MissingDigits));
+        assert_eq!(stream("99999999999999999999"), Err(TokenError::IntegerLiteralOverflow));
+        assert_eq!(stream(""), Ok(vec![]));
+
+        // The tokens before an error are read, and the stream ends after the error
+        let mut stream = TokenStream::new("a $ b");
+        assert_eq!(stream.next(), Some(Ok(Token::Ident("a".to_string()))));
+        assert_eq!(stream.next(), Some(Err(TokenError::UnknownChar('$'))));
+        assert_eq!(stream.next(), None);
+    }
 }